@@ -0,0 +1,41 @@
+//! Multi-threaded increments of `ShardedCounter`, compared to a single shared atomic.
+//!
+//! Run with `cargo bench --features std --bench sharded_counter`.
+use std::{
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
+use atomics::sharded_counter::ShardedCounter;
+
+const ADDS_PER_THREAD: usize = 1_000_000;
+
+fn run(threads: usize, add: impl Fn() + Sync) -> Duration {
+    let start = Instant::now();
+    std::thread::scope(|s| {
+        for _ in 0..threads {
+            s.spawn(|| {
+                for _ in 0..ADDS_PER_THREAD {
+                    add();
+                }
+            });
+        }
+    });
+    start.elapsed()
+}
+
+fn main() {
+    for threads in [1, 2, 4, 8] {
+        let single = AtomicUsize::new(0);
+        let single_time = run(threads, || {
+            single.fetch_add(1, Ordering::Relaxed);
+        });
+        assert_eq!(single.load(Ordering::Relaxed), threads * ADDS_PER_THREAD);
+
+        let sharded = ShardedCounter::<8>::new();
+        let sharded_time = run(threads, || sharded.add(1));
+        assert_eq!(sharded.sum(), threads * ADDS_PER_THREAD);
+
+        println!("{threads} threads: single atomic {single_time:?}, sharded {sharded_time:?}");
+    }
+}
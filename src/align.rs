@@ -0,0 +1,43 @@
+//! Alignment markers for the last (`A`) type param of the lock types.
+//!
+//! `A` is only used for a zero-sized `[A; 0]` field, so the lock takes the alignment of `A` (and its size is
+//! rounded up to it). Put e.g. `SpinMutexEx<S, T, Align64>` in an array to keep every lock on its own cache line.
+
+/// Natural alignment of the lock. Default for the `A` param.
+pub type NoAlign = ();
+
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(align(64))]
+pub struct Align64;
+
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(align(128))]
+pub struct Align128;
+
+#[cfg(test)]
+mod tests {
+    use core::mem::{align_of, size_of};
+
+    use super::*;
+    use crate::{
+        backoff::DEFAULT_SPIN_LIMIT as S, fair_spin_mutex::FairSpinMutexEx,
+        spin_mutex::SpinMutexEx, spin_rwlock::SpinRwLockEx, spin_seqlock::SpinSeqLockEx,
+    };
+
+    #[test]
+    fn lock_alignment() {
+        assert_eq!(align_of::<SpinMutexEx<S, u8, Align64>>(), 64);
+        assert_eq!(align_of::<SpinMutexEx<S, u8, Align128>>(), 128);
+        assert_eq!(size_of::<[SpinMutexEx<S, u8, Align64>; 2]>(), 128);
+        assert_eq!(
+            align_of::<SpinRwLockEx<S, u8, { isize::MAX }, Align64>>(),
+            64
+        );
+        assert_eq!(align_of::<SpinSeqLockEx<S, u8, usize, Align128>>(), 128);
+        assert_eq!(align_of::<FairSpinMutexEx<S, u8, Align64>>(), 64);
+        assert_eq!(
+            align_of::<SpinMutexEx<S, u8, NoAlign>>(),
+            align_of::<SpinMutexEx<S, u8>>()
+        );
+    }
+}
@@ -0,0 +1,134 @@
+//! Atomic `Option<T>` for types with a niche (e.g. `NonZeroU32`, `char`, `bool`), so `None` fits in the same
+//! integer as `T`.
+use core::{fmt, sync::atomic::Ordering};
+
+use crate::atomic_t::{self, NoPadding};
+
+macro_rules! impl_atomic_option {
+    ($($struct_name:ident, $atomic_t:ident);*;) => {
+        $(
+        /// `Option<T>` stored in an [`atomic_t`] type of the same width. `Option<T>` must be the same size as
+        /// `T` (checked at compile time), so `None` is stored in the niche of `T`, without padding bytes.
+        pub struct $struct_name<T: NoPadding> {
+            data: atomic_t::$atomic_t<Option<T>>,
+        }
+        impl<T: NoPadding> $struct_name<T> {
+            $crate::sync::loom_const_fn! {
+                #[inline]
+                pub fn new(value: Option<T>) -> Self {
+                    $crate::const_type_assert!(
+                        T,
+                        size_of::<Option<T>>() == size_of::<T>(),
+                        "Option<T> must have the same size as T (T must have a niche)",
+                    );
+                    // Safety: `T` has no padding, and `None` is stored in its niche, so neither has
+                    // `Option<T>`. `new` checks that it fits the atomic exactly.
+                    Self { data: unsafe { atomic_t::$atomic_t::new(value) } }
+                }
+            }
+            #[inline]
+            pub fn into_inner(self) -> Option<T> {
+                self.data.into_inner()
+            }
+            #[cfg(not(loom))]
+            #[inline]
+            pub fn get_mut(&mut self) -> &mut Option<T> {
+                self.data.get_mut()
+            }
+            #[inline]
+            pub fn load(&self, order: Ordering) -> Option<T> {
+                self.data.load(order)
+            }
+            #[inline]
+            pub fn store(&self, value: Option<T>, order: Ordering) {
+                self.data.store(value, order)
+            }
+            #[inline]
+            pub fn swap(&self, value: Option<T>, order: Ordering) -> Option<T> {
+                self.data.swap(value, order)
+            }
+            /// Replaces the value with `None`, and returns the previous one.
+            #[inline]
+            pub fn take(&self, order: Ordering) -> Option<T> {
+                self.data.swap(None, order)
+            }
+            #[inline]
+            pub fn compare_exchange(
+                &self,
+                current: Option<T>,
+                new: Option<T>,
+                success: Ordering,
+                failure: Ordering,
+            ) -> Result<Option<T>, Option<T>> {
+                self.data.compare_exchange(current, new, success, failure)
+            }
+            #[inline]
+            pub fn compare_exchange_weak(
+                &self,
+                current: Option<T>,
+                new: Option<T>,
+                success: Ordering,
+                failure: Ordering,
+            ) -> Result<Option<T>, Option<T>> {
+                self.data.compare_exchange_weak(current, new, success, failure)
+            }
+        }
+        impl<T: NoPadding> Default for $struct_name<T> {
+            #[inline]
+            fn default() -> Self {
+                Self::new(None)
+            }
+        }
+        impl<T: NoPadding> From<Option<T>> for $struct_name<T> {
+            #[inline]
+            fn from(value: Option<T>) -> Self {
+                Self::new(value)
+            }
+        }
+        impl<T: NoPadding + fmt::Debug> fmt::Debug for $struct_name<T> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                fmt::Debug::fmt(&self.load(Ordering::Relaxed), f)
+            }
+        }
+        )*
+    };
+}
+impl_atomic_option! {
+    AtomicOption8, AtomicT8;
+    AtomicOption16, AtomicT16;
+    AtomicOption32, AtomicT32;
+    AtomicOptionUsize, AtomicTUsize;
+}
+#[cfg(any(target_has_atomic = "64", feature = "portable-atomic"))]
+impl_atomic_option! {
+    AtomicOption64, AtomicT64;
+}
+
+#[cfg(test)]
+mod tests {
+    use core::num::NonZeroU32;
+
+    use super::*;
+
+    #[test]
+    fn non_zero_round_trips_some_and_none() {
+        let one = NonZeroU32::new(1);
+        let max = NonZeroU32::new(u32::MAX);
+        let a = AtomicOption32::new(one);
+        assert_eq!(a.load(Ordering::Relaxed), one);
+        a.store(None, Ordering::Relaxed);
+        assert_eq!(a.load(Ordering::Relaxed), None);
+        assert_eq!(a.swap(max, Ordering::Relaxed), None);
+        assert_eq!(
+            a.compare_exchange(one, None, Ordering::Relaxed, Ordering::Relaxed),
+            Err(max)
+        );
+        assert_eq!(
+            a.compare_exchange(max, one, Ordering::Relaxed, Ordering::Relaxed),
+            Ok(max)
+        );
+        assert_eq!(a.take(Ordering::Relaxed), one);
+        assert_eq!(a.take(Ordering::Relaxed), None);
+        assert_eq!(AtomicOption32::<NonZeroU32>::default().into_inner(), None);
+    }
+}
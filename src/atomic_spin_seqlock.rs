@@ -5,14 +5,23 @@ use core::{
     sync::atomic::{AtomicPtr, AtomicUsize, Ordering},
 };
 
-use crate::backoff::{Backoff, DEFAULT_SPIN_LIMIT};
+use crate::{
+    backoff::{Backoff, DEFAULT_SPIN_LIMIT},
+    contention_trace::ContentionTrace,
+    lock_hierarchy::LockLevel,
+    ordering_info::OrderingInfo,
+    spin_seqlock::{SeqLockVersion, record_torn_read},
+};
 
 const INIT_UNLOCKED: usize = 1;
 const LOCKED: usize = 0;
 
-pub struct SpinSeqLockAtomicPtrEx<const B: isize, T> {
+/// `A` sets the alignment of the lock, see [`align`](crate::align).
+pub struct SpinSeqLockAtomicPtrEx<const B: isize, T, A = ()> {
     ptr: AtomicPtr<T>,
     version: AtomicUsize,
+    level: LockLevel,
+    _align: [A; 0],
 }
 pub type SpinSeqLockAtomicPtr<T> = SpinSeqLockAtomicPtrEx<DEFAULT_SPIN_LIMIT, T>;
 pub type SpinSeqLockAtomicPtrReadGuard<'a, T> =
@@ -20,64 +29,86 @@ pub type SpinSeqLockAtomicPtrReadGuard<'a, T> =
 pub type SpinSeqLockAtomicPtrWriteGuard<'a, T> =
     SpinSeqLockAtomicPtrWriteGuardEx<'a, DEFAULT_SPIN_LIMIT, T>;
 
-pub struct SpinSeqLockAtomicPtrReadGuardEx<'a, const B: isize, T> {
-    cell: &'a SpinSeqLockAtomicPtrEx<B, T>,
+pub struct SpinSeqLockAtomicPtrReadGuardEx<'a, const B: isize, T, A = ()> {
+    cell: &'a SpinSeqLockAtomicPtrEx<B, T, A>,
     ptr_snapshoot: *mut T,
     prev: usize,
 }
-impl<const B: isize, T> Drop for SpinSeqLockAtomicPtrReadGuardEx<'_, B, T> {
+impl<const B: isize, T, A> Drop for SpinSeqLockAtomicPtrReadGuardEx<'_, B, T, A> {
     #[inline]
     fn drop(&mut self) {
         self.cell.version.store(self.prev, Ordering::Release);
+        self.cell.level.released();
     }
 }
-impl<const B: isize, T> Deref for SpinSeqLockAtomicPtrReadGuardEx<'_, B, T> {
+impl<const B: isize, T, A> Deref for SpinSeqLockAtomicPtrReadGuardEx<'_, B, T, A> {
     type Target = *mut T;
     #[inline]
     fn deref(&self) -> &Self::Target {
         &self.ptr_snapshoot
     }
 }
-pub struct SpinSeqLockAtomicPtrWriteGuardEx<'a, const B: isize, T> {
-    cell: &'a SpinSeqLockAtomicPtrEx<B, T>,
+pub struct SpinSeqLockAtomicPtrWriteGuardEx<'a, const B: isize, T, A = ()> {
+    cell: &'a SpinSeqLockAtomicPtrEx<B, T, A>,
     ptr_snapshoot: *mut T,
     next: usize,
 }
-impl<const B: isize, T> Drop for SpinSeqLockAtomicPtrWriteGuardEx<'_, B, T> {
+impl<const B: isize, T, A> Drop for SpinSeqLockAtomicPtrWriteGuardEx<'_, B, T, A> {
     #[inline]
     fn drop(&mut self) {
         self.cell.ptr.store(self.ptr_snapshoot, Ordering::Release);
         self.cell.version.store(self.next, Ordering::Release);
+        self.cell.level.released();
+    }
+}
+impl<'a, const B: isize, T, A> SpinSeqLockAtomicPtrWriteGuardEx<'a, B, T, A> {
+    /// Stores the pointer and turns this guard into a read guard, without unlocking in between.
+    ///
+    /// The lock stays held, so no writer can intervene, and the new version is published only when the
+    /// returned read guard is dropped.
+    #[inline]
+    pub fn downgrade(self) -> SpinSeqLockAtomicPtrReadGuardEx<'a, B, T, A> {
+        let this = mem::ManuallyDrop::new(self);
+        this.cell.ptr.store(this.ptr_snapshoot, Ordering::Release);
+        SpinSeqLockAtomicPtrReadGuardEx {
+            cell: this.cell,
+            ptr_snapshoot: this.ptr_snapshoot,
+            prev: this.next,
+        }
     }
 }
-impl<const B: isize, T> Deref for SpinSeqLockAtomicPtrWriteGuardEx<'_, B, T> {
+impl<const B: isize, T, A> Deref for SpinSeqLockAtomicPtrWriteGuardEx<'_, B, T, A> {
     type Target = *mut T;
     #[inline]
     fn deref(&self) -> &Self::Target {
         &self.ptr_snapshoot
     }
 }
-impl<const B: isize, T> DerefMut for SpinSeqLockAtomicPtrWriteGuardEx<'_, B, T> {
+impl<const B: isize, T, A> DerefMut for SpinSeqLockAtomicPtrWriteGuardEx<'_, B, T, A> {
     #[inline]
     fn deref_mut(&mut self) -> &mut Self::Target {
         &mut self.ptr_snapshoot
     }
 }
 
-impl<const B: isize, T> SpinSeqLockAtomicPtrEx<B, T> {
+impl<const B: isize, T, A> SpinSeqLockAtomicPtrEx<B, T, A> {
     #[inline]
-    pub fn read(&self) -> SpinSeqLockAtomicPtrReadGuardEx<'_, B, T> {
+    pub fn read(&self) -> SpinSeqLockAtomicPtrReadGuardEx<'_, B, T, A> {
         let mut backoff = Backoff::<B>::new();
+        let mut trace = ContentionTrace::new();
         loop {
             let Some(guard) = self.try_read() else {
+                trace.snoozed::<Self>();
                 backoff.snooze();
                 continue;
             };
+            trace.acquired::<Self>();
             return guard;
         }
     }
     #[inline]
-    pub fn try_read(&self) -> Option<SpinSeqLockAtomicPtrReadGuardEx<'_, B, T>> {
+    pub fn try_read(&self) -> Option<SpinSeqLockAtomicPtrReadGuardEx<'_, B, T, A>> {
+        self.level.check();
         let prev = self.version.load(Ordering::Relaxed);
 
         if prev != LOCKED
@@ -86,6 +117,7 @@ impl<const B: isize, T> SpinSeqLockAtomicPtrEx<B, T> {
                 .compare_exchange(prev, LOCKED, Ordering::Acquire, Ordering::Relaxed)
                 .is_ok()
         {
+            self.level.acquired();
             return Some(SpinSeqLockAtomicPtrReadGuardEx {
                 cell: self,
                 prev,
@@ -96,18 +128,22 @@ impl<const B: isize, T> SpinSeqLockAtomicPtrEx<B, T> {
     }
 
     #[inline]
-    pub fn write(&self) -> SpinSeqLockAtomicPtrWriteGuardEx<'_, B, T> {
+    pub fn write(&self) -> SpinSeqLockAtomicPtrWriteGuardEx<'_, B, T, A> {
         let mut backoff = Backoff::<B>::new();
+        let mut trace = ContentionTrace::new();
         loop {
             let Some(guard) = self.try_write() else {
+                trace.snoozed::<Self>();
                 backoff.snooze();
                 continue;
             };
+            trace.acquired::<Self>();
             return guard;
         }
     }
     #[inline]
-    pub fn try_write(&self) -> Option<SpinSeqLockAtomicPtrWriteGuardEx<'_, B, T>> {
+    pub fn try_write(&self) -> Option<SpinSeqLockAtomicPtrWriteGuardEx<'_, B, T, A>> {
+        self.level.check();
         let prev = self.version.load(Ordering::Relaxed);
 
         if prev != LOCKED
@@ -116,25 +152,29 @@ impl<const B: isize, T> SpinSeqLockAtomicPtrEx<B, T> {
                 .compare_exchange(prev, LOCKED, Ordering::Acquire, Ordering::Relaxed)
                 .is_ok()
         {
+            self.level.acquired();
             return Some(SpinSeqLockAtomicPtrWriteGuardEx {
                 cell: self,
-                next: prev + 1,
+                next: prev.next_version(),
                 ptr_snapshoot: self.ptr.load(Ordering::Acquire),
             });
         }
 
         None
     }
-    // #[inline]
-    // pub fn access<R>(&self, callback: impl FnOnce(&T) -> R) -> R {
-    //     let read_guard = self.read();
-    //     callback(&read_guard)
-    // }
-    // #[inline]
-    // pub fn access_mut<R>(&self, callback: impl FnOnce(&mut T) -> R) -> R {
-    //     let mut write_guard = self.write();
-    //     callback(&mut write_guard)
-    // }
+    /// Runs `callback` with the pointer, under the read lock.
+    #[inline]
+    pub fn access<R>(&self, callback: impl FnOnce(&*mut T) -> R) -> R {
+        let read_guard = self.read();
+        callback(&read_guard)
+    }
+    /// Runs `callback` with the pointer under the write lock. If `callback` replaces the pointer, the new one
+    /// is stored when the lock is released.
+    #[inline]
+    pub fn access_mut<R>(&self, callback: impl FnOnce(&mut *mut T) -> R) -> R {
+        let mut write_guard = self.write();
+        callback(&mut write_guard)
+    }
 
     #[inline]
     fn optimistic_read(&self) -> Option<*mut T> {
@@ -146,14 +186,14 @@ impl<const B: isize, T> SpinSeqLockAtomicPtrEx<B, T> {
                 if self.version.load(Ordering::Relaxed) == version {
                     return Some(data);
                 }
+                record_torn_read();
             }
         }
         None
     }
     #[inline]
     pub fn load_maybe_outdated(&self) -> *mut T {
-        let data = self.ptr.load(Ordering::Relaxed);
-        data
+        self.ptr.load(Ordering::Relaxed)
     }
     #[inline]
     pub fn load(&self) -> *mut T {
@@ -163,12 +203,68 @@ impl<const B: isize, T> SpinSeqLockAtomicPtrEx<B, T> {
     pub fn store(&self, v: *mut T) {
         *self.write() = v;
     }
+    /// Loads the pointer together with the version it was published with, for `compare_exchange_versioned`.
+    #[inline]
+    pub fn load_versioned(&self) -> (usize, *mut T) {
+        let mut backoff = Backoff::<B>::new();
+        loop {
+            let version = self.version.load(Ordering::Acquire);
+            if version != LOCKED {
+                let data = self.ptr.load(Ordering::Acquire);
+                if self.version.load(Ordering::Relaxed) == version {
+                    return (version, data);
+                }
+            }
+            backoff.snooze();
+        }
+    }
+    /// Stores `new` only if the pointer is `current` and the version is still `expected_version` (from
+    /// `load_versioned`). On failure, returns the current version and pointer.
+    ///
+    /// Every write bumps the version, so this fails even if the pointer was changed and then changed back to
+    /// `current` in the meantime (ABA).
+    #[inline]
+    pub fn compare_exchange_versioned(
+        &self,
+        current: *mut T,
+        expected_version: usize,
+        new: *mut T,
+    ) -> Result<(), (usize, *mut T)> {
+        self.level.check();
+        if expected_version == LOCKED
+            || self
+                .version
+                .compare_exchange(
+                    expected_version,
+                    LOCKED,
+                    Ordering::Acquire,
+                    Ordering::Relaxed,
+                )
+                .is_err()
+        {
+            return Err(self.load_versioned());
+        }
+        self.level.acquired();
+        let data = self.ptr.load(Ordering::Relaxed);
+        let result = if data == current {
+            self.ptr.store(new, Ordering::Release);
+            self.version
+                .store(expected_version.next_version(), Ordering::Release);
+            Ok(())
+        } else {
+            // Nothing was written, so we can restore the version
+            self.version.store(expected_version, Ordering::Release);
+            Err((expected_version, data))
+        };
+        self.level.released();
+        result
+    }
 }
 
-// unsafe impl<const B: isize, T> Send for AtomicPtrSpinSeqLockEx<B, T> {}
-// unsafe impl<const B: isize, T> Sync for AtomicPtrSpinSeqLockEx<B, T> {}
+// unsafe impl<const B: isize, T, A> Send for AtomicPtrSpinSeqLockEx<B, T, A> {}
+// unsafe impl<const B: isize, T, A> Sync for AtomicPtrSpinSeqLockEx<B, T, A> {}
 
-impl<const B: isize, T> SpinSeqLockAtomicPtrEx<B, T> {
+impl<const B: isize, T, A> SpinSeqLockAtomicPtrEx<B, T, A> {
     #[inline]
     pub fn swap(&self, other: &mut *mut T) {
         mem::swap(&mut *self.write(), other)
@@ -181,13 +277,54 @@ impl<const B: isize, T> SpinSeqLockAtomicPtrEx<B, T> {
     pub fn replace(&self, other: *mut T) -> *mut T {
         mem::replace(&mut *self.write(), other)
     }
+    /// Replaces the pointer with `f(old)` under the write lock, and returns `(old, new)`.
+    ///
+    /// New pointer is stored before the version is bumped, so optimistic readers (`load`) either see `old`
+    /// with the previous version, or `new` with the bumped one.
+    #[inline]
+    pub fn modify(&self, f: impl FnOnce(*mut T) -> *mut T) -> (*mut T, *mut T) {
+        let mut guard = self.write();
+        let old = *guard;
+        let new = f(old);
+        *guard = new;
+        (old, new)
+    }
+    /// Links `node` as the new head of the list, under the write lock.
+    ///
+    /// `link` is called with the old head, and has to store it as the `next` pointer of `node`.
+    #[inline]
+    pub fn prepend(&self, node: *mut T, link: impl FnOnce(*mut T)) {
+        let mut guard = self.write();
+        link(*guard);
+        *guard = node;
+    }
+    /// Steals the whole list, by swapping the head with null under a single write lock.
+    ///
+    /// Caller owns the returned chain, and is responsible for freeing its nodes.
+    #[inline]
+    pub fn take_all(&self) -> *mut T {
+        self.replace(ptr::null_mut())
+    }
     #[inline]
     pub const fn new(val: *mut T) -> Self {
         Self {
             ptr: AtomicPtr::new(val),
             version: AtomicUsize::new(INIT_UNLOCKED),
+            level: LockLevel::NONE,
+            _align: [],
         }
     }
+    /// Assigns a level to this lock for the `lock-hierarchy` validation. No-op without the feature.
+    #[inline]
+    pub const fn with_level(mut self, level: usize) -> Self {
+        self.level = LockLevel::new(level);
+        self
+    }
+    /// Memory orderings used by this lock, see [`OrderingInfo`].
+    #[inline]
+    pub const fn ordering_info() -> OrderingInfo {
+        OrderingInfo::SEQLOCK
+    }
     #[inline]
     pub fn into_inner(self) -> *mut T {
         self.ptr.into_inner()
@@ -201,36 +338,220 @@ impl<const B: isize, T> SpinSeqLockAtomicPtrEx<B, T> {
     }
     #[inline]
     pub fn take_mut(&mut self) -> *mut T {
-        mem::take(&mut self.get_mut())
+        mem::take(self.get_mut())
     }
 }
-// impl<const B: isize, T> AtomicPtrSpinSeqLockEx<B, T> {
+#[cfg(feature = "alloc")]
+impl<const B: isize, T, A> SpinSeqLockAtomicPtrEx<B, T, A> {
+    /// Stores the pointer from `Box::into_raw`. Reclaim it with `into_box`.
+    #[inline]
+    pub fn from_box(b: alloc::boxed::Box<T>) -> Self {
+        Self::new(alloc::boxed::Box::into_raw(b))
+    }
+    /// Returns `None` if the stored pointer is null.
+    ///
+    /// # Safety
+    /// Stored pointer must be null or come from `Box::into_raw` (e.g. via `from_box`), and must not be owned
+    /// by anything else.
+    #[inline]
+    pub unsafe fn into_box(self) -> Option<alloc::boxed::Box<T>> {
+        let ptr = self.into_inner();
+        // Safety: guaranteed by the caller
+        (!ptr.is_null()).then(|| unsafe { alloc::boxed::Box::from_raw(ptr) })
+    }
+}
+// impl<const B: isize, T, A> AtomicPtrSpinSeqLockEx<B, T, A> {
 //     #[inline]
 // }
-impl<const B: isize, T: Default> Default for SpinSeqLockAtomicPtrEx<B, T> {
+impl<const B: isize, T: Default, A> Default for SpinSeqLockAtomicPtrEx<B, T, A> {
     #[inline]
     fn default() -> Self {
         Self::new(ptr::null_mut())
     }
 }
-impl<const B: isize, T> Clone for SpinSeqLockAtomicPtrEx<B, T> {
+impl<const B: isize, T, A> Clone for SpinSeqLockAtomicPtrEx<B, T, A> {
     #[inline]
     fn clone(&self) -> Self {
         let data = self.load();
         Self::new(data)
     }
 }
-impl<const B: isize, T: core::fmt::Debug + Copy> core::fmt::Debug for SpinSeqLockAtomicPtrEx<B, T> {
+impl<const B: isize, T: core::fmt::Debug + Copy, A> core::fmt::Debug
+    for SpinSeqLockAtomicPtrEx<B, T, A>
+{
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         f.debug_struct("AtomicCellOpt")
             .field("data", &self.load())
             .finish()
     }
 }
-impl<const B: isize, T: PartialEq + Copy> PartialEq for SpinSeqLockAtomicPtrEx<B, T> {
+impl<const B: isize, T: PartialEq + Copy, A> PartialEq for SpinSeqLockAtomicPtrEx<B, T, A> {
     #[inline]
     fn eq(&self, other: &Self) -> bool {
         self.load() == other.load()
     }
 }
-impl<const B: isize, T: Eq + Copy> Eq for SpinSeqLockAtomicPtrEx<B, T> {}
+impl<const B: isize, T: Eq + Copy, A> Eq for SpinSeqLockAtomicPtrEx<B, T, A> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn modify_returns_old_and_new() {
+        let mut items = [1u32, 2, 3];
+        let base = items.as_mut_ptr();
+        let lock = SpinSeqLockAtomicPtr::new(base);
+        let (version, _) = lock.load_versioned();
+        // Safety: stays within `items`
+        let (old, new) = lock.modify(|p| unsafe { p.add(1) });
+        assert_eq!(old, base);
+        assert_eq!(new, base.wrapping_add(1));
+        assert_eq!(lock.load(), new);
+        assert_eq!(lock.load_versioned(), (version.next_version(), new));
+    }
+
+    struct Node {
+        value: usize,
+        next: *mut Node,
+    }
+
+    #[test]
+    fn multi_producer_prepend_take_all() {
+        const PRODUCERS: usize = 4;
+        const NODES: usize = 500;
+        let head = SpinSeqLockAtomicPtr::<Node>::new(ptr::null_mut());
+        let mut sum = 0;
+        let mut count = 0;
+        let mut drain = |chain: *mut Node| {
+            let mut node = chain;
+            while !node.is_null() {
+                // Safety: the nodes come from `Box::into_raw`, and the chain is owned by us after `take_all`
+                let boxed = unsafe { std::boxed::Box::from_raw(node) };
+                sum += boxed.value;
+                count += 1;
+                node = boxed.next;
+            }
+        };
+        std::thread::scope(|s| {
+            for p in 0..PRODUCERS {
+                let head = &head;
+                s.spawn(move || {
+                    for i in 0..NODES {
+                        let node = std::boxed::Box::into_raw(std::boxed::Box::new(Node {
+                            value: p * NODES + i,
+                            next: ptr::null_mut(),
+                        }));
+                        // Safety: `node` is not shared until it's linked
+                        head.prepend(node, |next| unsafe { (*node).next = next });
+                    }
+                });
+            }
+            for _ in 0..100 {
+                drain(head.take_all());
+            }
+        });
+        drain(head.take_all());
+        let total = PRODUCERS * NODES;
+        assert_eq!(count, total);
+        assert_eq!(sum, total * (total - 1) / 2);
+        assert!(head.load().is_null());
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn from_box_into_box() {
+        let lock = SpinSeqLockAtomicPtr::from_box(alloc::boxed::Box::new(5u32));
+        // Safety: the pointer comes from `from_box`
+        let b = unsafe { lock.into_box() }.unwrap();
+        assert_eq!(*b, 5);
+
+        let lock = SpinSeqLockAtomicPtr::<u32>::new(ptr::null_mut());
+        // Safety: the pointer is null
+        assert!(unsafe { lock.into_box() }.is_none());
+    }
+
+    #[test]
+    fn compare_exchange_versioned_rejects_aba() {
+        let mut items = [1u32, 2];
+        let a = &raw mut items[0];
+        let b = &raw mut items[1];
+        let lock = SpinSeqLockAtomicPtr::new(a);
+        let (version, current) = lock.load_versioned();
+        assert_eq!(current, a);
+
+        // A -> B -> A in between the load and the CAS.
+        lock.store(b);
+        lock.store(a);
+        let after_aba = lock.load_versioned();
+        assert_eq!(after_aba.1, a);
+        assert_eq!(
+            lock.compare_exchange_versioned(a, version, b),
+            Err(after_aba)
+        );
+        assert_eq!(lock.load(), a);
+
+        // Pointer mismatch with the right version doesn't bump the version.
+        assert_eq!(
+            lock.compare_exchange_versioned(b, after_aba.0, b),
+            Err(after_aba)
+        );
+        assert_eq!(lock.compare_exchange_versioned(a, after_aba.0, b), Ok(()));
+        assert_eq!(lock.load_versioned(), (after_aba.0.next_version(), b));
+    }
+
+    #[test]
+    fn downgrade_keeps_lock_held() {
+        let mut items = [1u32, 2];
+        let a = &raw mut items[0];
+        let b = &raw mut items[1];
+        let lock = SpinSeqLockAtomicPtr::new(a);
+        let (version, _) = lock.load_versioned();
+
+        let mut guard = lock.write();
+        *guard = b;
+        let read_guard = guard.downgrade();
+        assert_eq!(*read_guard, b);
+        assert_eq!(lock.load_maybe_outdated(), b);
+        std::thread::scope(|s| {
+            s.spawn(|| assert!(lock.try_write().is_none()));
+        });
+        drop(read_guard);
+        assert_eq!(lock.load_versioned(), (version.next_version(), b));
+    }
+
+    #[test]
+    fn default_is_null() {
+        assert!(SpinSeqLockAtomicPtr::<u32>::default().load().is_null());
+    }
+
+    #[test]
+    fn write_at_max_version_wraps_to_unlocked() {
+        let mut items = [1u32, 2];
+        let a = &raw mut items[0];
+        let b = &raw mut items[1];
+        let lock = SpinSeqLockAtomicPtr::new(a);
+        lock.version.store(usize::MAX, Ordering::Relaxed);
+
+        *lock.try_write().unwrap() = b;
+        assert_eq!(lock.version.load(Ordering::Relaxed), INIT_UNLOCKED);
+        assert_eq!(lock.load_versioned(), (INIT_UNLOCKED, b));
+        lock.store(a);
+        assert_eq!(lock.load_versioned(), (INIT_UNLOCKED + 1, a));
+    }
+
+    #[test]
+    fn access_mut_replaces_pointer() {
+        let mut items = [1u32, 2];
+        let a = &raw mut items[0];
+        let b = &raw mut items[1];
+        let lock = SpinSeqLockAtomicPtr::new(a);
+        let (version, _) = lock.load_versioned();
+
+        let prev = lock.access_mut(|ptr| mem::replace(ptr, b));
+        assert_eq!(prev, a);
+        // Safety: `b` points into `items`, that outlives the lock
+        assert_eq!(lock.access(|ptr| unsafe { **ptr }), 2);
+        assert_eq!(lock.load_versioned(), (version.next_version(), b));
+    }
+}
@@ -0,0 +1,259 @@
+//! `AtomicStorage<T>` wraps the most efficient concurrent cell for `T`, behind one `load`/`store` interface.
+//!
+//! Primitives resolve to `atomic_t::AtomicT*` of the same width (they have no padding, so it's safe), and
+//! types that don't fit an atomic (arrays, `u128`, ...) resolve to `SpinSeqLock`. Stable Rust can't pick the
+//! backend from `size_of::<T>()` for arbitrary types, so other types opt in with a one-line impl:
+//! `impl HasAtomicStorage for MyType { type Impl = SpinSeqLock<MyType>; }` (or an `atomic_t_mu::AtomicT*`).
+//!
+//! Loads are `Acquire`, stores are `Release` and swaps are `AcqRel`.
+use core::{mem, sync::atomic::Ordering};
+
+use crate::{
+    atomic_t, atomic_t_mu,
+    spin_mutex::SpinMutexEx,
+    spin_rwlock::SpinRwLockEx,
+    spin_seqlock::{SeqLockVersion, SpinSeqLock, SpinSeqLockEx},
+};
+
+/// Common interface of the crate's concurrent cells, for generic code over "some concurrent cell of `T`".
+///
+/// Implemented by `atomic_t_mu::AtomicT*`, `atomic_t::AtomicT*` (for primitives), `SpinSeqLockEx`, `SpinMutexEx`
+/// and `SpinRwLockEx`.
+pub trait AtomicCell<T: Copy> {
+    fn new(value: T) -> Self;
+    fn load(&self) -> T;
+    fn store(&self, value: T);
+    fn swap(&self, value: T) -> T;
+}
+pub trait HasAtomicStorage: Copy {
+    type Impl: AtomicCell<Self>;
+}
+
+/// Concurrent cell backed by the `HasAtomicStorage::Impl` of `T`.
+pub struct AtomicStorage<T: HasAtomicStorage> {
+    inner: T::Impl,
+}
+impl<T: HasAtomicStorage> AtomicStorage<T> {
+    #[inline]
+    pub fn new(value: T) -> Self {
+        Self {
+            inner: T::Impl::new(value),
+        }
+    }
+    #[inline]
+    pub fn load(&self) -> T {
+        self.inner.load()
+    }
+    #[inline]
+    pub fn store(&self, value: T) {
+        self.inner.store(value)
+    }
+    #[inline]
+    pub fn swap(&self, value: T) -> T {
+        self.inner.swap(value)
+    }
+    /// Backend cell, for the operations that are specific to it.
+    #[inline]
+    pub fn inner(&self) -> &T::Impl {
+        &self.inner
+    }
+}
+impl<T: HasAtomicStorage + Default> Default for AtomicStorage<T> {
+    #[inline]
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+impl<T: HasAtomicStorage + core::fmt::Debug> core::fmt::Debug for AtomicStorage<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        core::fmt::Debug::fmt(&self.load(), f)
+    }
+}
+
+impl<const B: isize, T: Copy, V: SeqLockVersion, A> AtomicCell<T> for SpinSeqLockEx<B, T, V, A> {
+    #[inline]
+    fn new(value: T) -> Self {
+        Self::new(value)
+    }
+    #[inline]
+    fn load(&self) -> T {
+        self.load()
+    }
+    #[inline]
+    fn store(&self, value: T) {
+        self.store(value)
+    }
+    #[inline]
+    fn swap(&self, value: T) -> T {
+        self.replace(value)
+    }
+}
+impl<const S: isize, T: Copy, A> AtomicCell<T> for SpinMutexEx<S, T, A> {
+    #[inline]
+    fn new(value: T) -> Self {
+        Self::new(value)
+    }
+    #[inline]
+    fn load(&self) -> T {
+        *self.lock()
+    }
+    #[inline]
+    fn store(&self, value: T) {
+        *self.lock() = value;
+    }
+    #[inline]
+    fn swap(&self, value: T) -> T {
+        mem::replace(&mut *self.lock(), value)
+    }
+}
+impl<const S: isize, T: Copy, const R: isize, A, const W: usize> AtomicCell<T>
+    for SpinRwLockEx<S, T, R, A, W>
+{
+    #[inline]
+    fn new(value: T) -> Self {
+        Self::new(value)
+    }
+    #[inline]
+    fn load(&self) -> T {
+        *self.read()
+    }
+    #[inline]
+    fn store(&self, value: T) {
+        *self.write() = value;
+    }
+    #[inline]
+    fn swap(&self, value: T) -> T {
+        self.replace(value)
+    }
+}
+
+macro_rules! impl_atomic_cell {
+  ($($atomic:ident),*) => {
+    $(
+    impl<T: Copy> AtomicCell<T> for atomic_t_mu::$atomic<T> {
+      #[inline]
+      fn new(value: T) -> Self { Self::new(value) }
+      #[inline]
+      fn load(&self) -> T { self.load(Ordering::Acquire) }
+      #[inline]
+      fn store(&self, value: T) { self.store(value, Ordering::Release) }
+      #[inline]
+      fn swap(&self, value: T) -> T { self.swap(value, Ordering::AcqRel) }
+    }
+    )*
+  };
+}
+impl_atomic_cell!(AtomicT8, AtomicT16, AtomicT32, AtomicTUsize);
+#[cfg(target_has_atomic = "64")]
+impl_atomic_cell!(AtomicT64);
+
+macro_rules! impl_primitive_storage {
+  ($atomic:ident: $($t:ty),*) => {
+    $(
+    impl AtomicCell<$t> for atomic_t::$atomic<$t> {
+      #[inline]
+      fn new(value: $t) -> Self {
+        // Safety: primitives don't have padding bytes
+        unsafe { Self::new(value) }
+      }
+      #[inline]
+      fn load(&self) -> $t { self.load(Ordering::Acquire) }
+      #[inline]
+      fn store(&self, value: $t) { self.store(value, Ordering::Release) }
+      #[inline]
+      fn swap(&self, value: $t) -> $t { self.swap(value, Ordering::AcqRel) }
+    }
+    impl HasAtomicStorage for $t {
+      type Impl = atomic_t::$atomic<$t>;
+    }
+    )*
+  };
+}
+impl_primitive_storage!(AtomicT8: u8, i8, bool);
+impl_primitive_storage!(AtomicT16: u16, i16);
+impl_primitive_storage!(AtomicT32: u32, i32, f32, char);
+impl_primitive_storage!(AtomicTUsize: usize, isize);
+#[cfg(target_has_atomic = "64")]
+impl_primitive_storage!(AtomicT64: u64, i64, f64);
+#[cfg(not(target_has_atomic = "64"))]
+impl HasAtomicStorage for u64 {
+    type Impl = SpinSeqLock<u64>;
+}
+#[cfg(not(target_has_atomic = "64"))]
+impl HasAtomicStorage for i64 {
+    type Impl = SpinSeqLock<i64>;
+}
+#[cfg(not(target_has_atomic = "64"))]
+impl HasAtomicStorage for f64 {
+    type Impl = SpinSeqLock<f64>;
+}
+impl HasAtomicStorage for u128 {
+    type Impl = SpinSeqLock<u128>;
+}
+impl HasAtomicStorage for i128 {
+    type Impl = SpinSeqLock<i128>;
+}
+impl<T: Copy, const N: usize> HasAtomicStorage for [T; N] {
+    type Impl = SpinSeqLock<[T; N]>;
+}
+
+#[cfg(test)]
+mod tests {
+    use core::any::TypeId;
+
+    use super::*;
+
+    fn backend<T: HasAtomicStorage>() -> TypeId
+    where
+        T::Impl: 'static,
+    {
+        TypeId::of::<T::Impl>()
+    }
+
+    #[test]
+    fn word_sized_uses_atomic() {
+        assert_eq!(backend::<u32>(), TypeId::of::<atomic_t::AtomicT32<u32>>());
+        let cell = AtomicStorage::new(1u32);
+        assert_eq!(cell.swap(2), 1);
+        cell.store(3);
+        assert_eq!(cell.load(), 3);
+    }
+
+    #[test]
+    fn large_uses_seqlock() {
+        assert_eq!(backend::<[u64; 4]>(), TypeId::of::<SpinSeqLock<[u64; 4]>>());
+        let cell = AtomicStorage::new([1u64; 4]);
+        assert_eq!(cell.swap([2; 4]), [1; 4]);
+        cell.store([3; 4]);
+        assert_eq!(cell.load(), [3; 4]);
+        assert_eq!(cell.inner().load(), [3; 4]);
+    }
+
+    #[test]
+    fn opt_in_type() {
+        #[derive(Clone, Copy, PartialEq, Debug)]
+        struct Pair(u16, u16);
+        impl HasAtomicStorage for Pair {
+            type Impl = atomic_t_mu::AtomicT32<Pair>;
+        }
+        let cell = AtomicStorage::new(Pair(1, 2));
+        assert_eq!(cell.swap(Pair(3, 4)), Pair(1, 2));
+        assert_eq!(cell.load(), Pair(3, 4));
+    }
+
+    fn exercise<C: AtomicCell<[u16; 2]>>() {
+        let cell = C::new([1, 2]);
+        assert_eq!(cell.load(), [1, 2]);
+        cell.store([3, 4]);
+        assert_eq!(cell.swap([5, 6]), [3, 4]);
+        assert_eq!(cell.load(), [5, 6]);
+    }
+
+    #[test]
+    fn generic_over_cells() {
+        exercise::<atomic_t_mu::AtomicT32<[u16; 2]>>();
+        exercise::<SpinSeqLock<[u16; 2]>>();
+        exercise::<crate::spin_mutex::SpinMutex<[u16; 2]>>();
+        exercise::<crate::spin_rwlock::SpinRwLock<[u16; 2]>>();
+    }
+}
@@ -1,10 +1,99 @@
 use core::{
     fmt,
     marker::PhantomData,
-    mem,
-    sync::atomic::{AtomicU8, AtomicU16, AtomicU32, AtomicU64, AtomicUsize, Ordering},
+    mem, ptr,
+    sync::atomic::Ordering,
 };
 
+use crate::sync::loom_const_fn;
+
+// With `portable-atomic`, the wrappers are backed by `portable_atomic` types, so they also work on targets
+// without native atomics of some width, or without CAS. Otherwise by `crate::sync` ones (`loom`'s under
+// `--cfg loom`).
+#[cfg(all(any(target_has_atomic = "64", loom), not(feature = "portable-atomic")))]
+use crate::sync::AtomicU64;
+#[cfg(not(feature = "portable-atomic"))]
+use crate::sync::{AtomicBool, AtomicU8, AtomicU16, AtomicU32, AtomicUsize};
+#[cfg(feature = "portable-atomic")]
+use portable_atomic::{AtomicBool, AtomicU8, AtomicU16, AtomicU32, AtomicU64, AtomicUsize};
+
+mod sealed {
+    /// Every `AtomicInt` fits in an `i128`, which is used to do the modular arithmetic without overflows.
+    pub trait Sealed {
+        const SIGNED: bool;
+        fn to_i128(self) -> i128;
+        fn from_i128(value: i128) -> Self;
+    }
+    pub trait NoPadding {}
+}
+/// Primitive integers, that get the `fetch_add`/`fetch_sub`/`fetch_and`/`fetch_or`/`fetch_xor`/`fetch_max`/
+/// `fetch_min` methods on the `AtomicT*` of the same width. Sealed, so arithmetic is never done on arbitrary
+/// `Copy` bit patterns.
+pub trait AtomicInt: Copy + sealed::Sealed {}
+/// Primitives (and their `NonZero` variants) without padding bytes, that can be stored with the safe
+/// `new_safe` constructor of the `AtomicT*` of the same width, without the `bytemuck` feature. Sealed, since
+/// the compiler can't check that a type has no padding.
+///
+/// ```
+/// use atomics::atomic_t::AtomicT32;
+/// use core::sync::atomic::Ordering;
+///
+/// let counter = AtomicT32::<u32>::new_safe(0);
+/// counter.store(7, Ordering::Relaxed);
+/// assert_eq!(counter.load(Ordering::Relaxed), 7);
+/// ```
+///
+/// Types with padding don't implement it, so they still need the unsafe `new`:
+///
+/// ```compile_fail,E0277
+/// use atomics::atomic_t::AtomicT32;
+///
+/// #[derive(Clone, Copy)]
+/// #[repr(C)]
+/// struct Padded(u8, u16);
+///
+/// let padded = AtomicT32::new_safe(Padded(1, 2));
+/// ```
+pub trait NoPadding: Copy + sealed::NoPadding {}
+macro_rules! impl_no_padding {
+  ($($t:ty),*) => {
+    $(
+    impl sealed::NoPadding for $t {}
+    impl NoPadding for $t {}
+    )*
+  };
+}
+impl_no_padding!(u8, i8, u16, i16, u32, i32, u64, i64, u128, i128, usize, isize, f32, f64, bool, char);
+impl_no_padding!(
+  core::num::NonZeroU8, core::num::NonZeroI8, core::num::NonZeroU16, core::num::NonZeroI16,
+  core::num::NonZeroU32, core::num::NonZeroI32, core::num::NonZeroU64, core::num::NonZeroI64,
+  core::num::NonZeroU128, core::num::NonZeroI128, core::num::NonZeroUsize, core::num::NonZeroIsize
+);
+// Load ordering of a read-modify-write with `order`, for the failure case of `fetch_update`.
+#[inline]
+fn fetch_ordering(order: Ordering) -> Ordering {
+    match order {
+        Ordering::Release => Ordering::Relaxed,
+        Ordering::AcqRel => Ordering::Acquire,
+        order => order,
+    }
+}
+macro_rules! impl_atomic_int {
+  ($($int:ty),*) => {
+    $(
+    impl sealed::Sealed for $int {
+      const SIGNED: bool = <$int>::MIN != 0;
+      #[inline]
+      fn to_i128(self) -> i128 { self as i128 }
+      #[inline]
+      fn from_i128(value: i128) -> Self { value as $int }
+    }
+    impl AtomicInt for $int {}
+    )*
+  };
+}
+impl_atomic_int!(u8, i8, u16, i16, u32, i32, u64, i64, usize, isize);
+
 macro_rules! impl_atomic_t {
   ($($struct_name:ident, $atomic:ty, $int:ty);*;) => {
     $(
@@ -16,11 +105,23 @@ macro_rules! impl_atomic_t {
 
     #[cfg(feature = "bytemuck")]
     impl <T:bytemuck::NoUninit+Copy> $struct_name<T>{
-      #[inline]
-      pub const fn new_no_uninit(value:T)->Self{
-        // # Safety: bytemuck::NoUninit guarantees no padding
-        unsafe{
-          Self::new(value)
+      $crate::sync::loom_const_fn! {
+        #[inline]
+        pub fn new_no_uninit(value:T)->Self{
+          // # Safety: bytemuck::NoUninit guarantees no padding
+          unsafe{
+            Self::new(value)
+          }
+        }
+      }
+    }
+    impl<T: NoPadding> $struct_name<T> {
+      $crate::sync::loom_const_fn! {
+        /// Safe `new`, for primitives without padding.
+        #[inline]
+        pub fn new_safe(value: T) -> Self {
+          // Safety: `NoPadding` types have no padding bytes
+          unsafe { Self::new(value) }
         }
       }
     }
@@ -28,8 +129,8 @@ macro_rules! impl_atomic_t {
       const fn transmute_to_t(value: $int) -> T {
         $crate::const_type_assert!(
           T,
-          mem::size_of::<T>() == mem::size_of::<$int>(),
-          "Size of T must be same as in the name of the container",
+          mem::size_of::<T>() <= mem::size_of::<$int>(),
+          "Size of T must be <= than in the name of the container",
         );
         $crate::const_type_assert!(
           T,
@@ -39,13 +140,13 @@ macro_rules! impl_atomic_t {
 
         // Safety:
         // As long as `value` was produced by transmuting T -> $int, inverse direction is OK
-        unsafe { mem::transmute_copy(&value) }
+        unsafe { ptr::read(ptr::from_ref(&value).cast::<T>()) }
       }
       const fn transmute_to_u(value: T) -> $int {
         $crate::const_type_assert!(
           T,
-          mem::size_of::<T>() == mem::size_of::<$int>(),
-          "Size of T must be same as in the name of the container",
+          mem::size_of::<T>() <= mem::size_of::<$int>(),
+          "Size of T must be <= than in the name of the container",
         );
         $crate::const_type_assert!(
           T,
@@ -53,20 +154,54 @@ macro_rules! impl_atomic_t {
           "Align of T must be <= than the name of the container",
         );
 
+        let mut data: $int = 0;
         // Safety:
-        // As long as T does not contain any padding bytes, this transmute is OK
-        unsafe { mem::transmute_copy(&value) }
+        // As long as T does not contain any padding bytes, this transmute is OK. If T is smaller, the
+        // remaining bytes stay zeroed.
+        unsafe {
+          ptr::copy_nonoverlapping(
+            ptr::from_ref(&value).cast::<u8>(), ptr::from_mut(&mut data).cast::<u8>(), mem::size_of::<T>(),
+          )
+        };
+        data
+      }
+      const fn assert_exact_size() {
+        $crate::const_type_assert!(
+          T,
+          mem::size_of::<T>() == mem::size_of::<$int>(),
+          "Size of T must be same as in the name of the container",
+        );
       }
 
-      /// # Safety
-      /// `T` cannot have any padding bytes
-      #[inline]
-      pub const unsafe fn new(value: T) -> Self {
-        let data = Self::transmute_to_u(value);
+      $crate::sync::loom_const_fn! {
+        /// # Safety
+        /// `T` cannot have any padding bytes
+        #[inline]
+        pub unsafe fn new(value: T) -> Self {
+          Self::assert_exact_size();
+          // Safety: same requirements as `new`
+          unsafe { Self::new_unchecked_size(value) }
+        }
+      }
+      $crate::sync::loom_const_fn! {
+        /// Like `new`, but `T` may be smaller than the container. The value is stored in the low-address bytes
+        /// and the remaining bytes are zeroed. `T` that is bigger or more aligned than the container still
+        /// fails to compile.
+        ///
+        /// # Safety
+        /// Same as `new`: `T` cannot have any padding bytes. In addition, anything that accesses the whole
+        /// container through `as_ptr` (e.g. over FFI) must keep the trailing bytes zeroed. Otherwise the
+        /// comparisons in `compare_exchange` and friends spuriously fail. The integer value seen that way
+        /// depends on endianness.
+        #[inline]
+        pub unsafe fn new_unchecked_size(value: T) -> Self {
+          let data = Self::transmute_to_u(value);
 
-        Self { data: <$atomic>::new(data), _pd: PhantomData }
+          Self { data: <$atomic>::new(data), _pd: PhantomData }
+        }
       }
-       #[inline]
+      #[cfg(not(loom))]
+      #[inline]
       pub fn get_mut(&mut self) -> &mut T { unsafe { &mut *(self.data.get_mut() as *mut $int as *mut T) } }
 
       #[inline]
@@ -82,6 +217,7 @@ macro_rules! impl_atomic_t {
       pub fn swap(&self, value: T, order: Ordering) -> T {
         Self::transmute_to_t(self.data.swap(Self::transmute_to_u(value), order))
       }
+      #[cfg(not(loom))]
       #[inline]
       pub fn swap_mut(&mut self,value: T) -> T {
         mem::replace(self.get_mut(), value)
@@ -125,10 +261,181 @@ macro_rules! impl_atomic_t {
           .map(|s| Self::transmute_to_t(s))
           .map_err(|e| Self::transmute_to_t(e))
       }
+      #[cfg(not(loom))]
       #[inline]
       pub fn as_ptr(&self) -> *mut T { self.data.as_ptr() as *mut T }
+
+      /// Tries to `compare_exchange` every element from `current` to `new`, returning the number of elements
+      /// that were swapped. Each element is exchanged independently, this is not a transaction.
+      #[inline]
+      pub fn compare_exchange_all(
+        slice: &[Self], current: T, new: T, success: Ordering, failure: Ordering,
+      ) -> usize {
+        slice.iter().filter(|a| a.compare_exchange(current, new, success, failure).is_ok()).count()
+      }
     }
 
+    impl<T: PartialEq + Copy> $struct_name<T> {
+      /// Same as `fetch_update`, but when `f` returns a value equal (`PartialEq`) to the current one, the store
+      /// is skipped, so the cache line is not dirtied by no-op updates.
+      #[inline]
+      pub fn fetch_update_if_changed(
+        &self, set_order: Ordering, fetch_order: Ordering, mut f: impl FnMut(T) -> Option<T>,
+      ) -> Result<T, T> {
+        let mut prev = self.load(fetch_order);
+        while let Some(next) = f(prev) {
+          if next == prev {
+            return Ok(prev);
+          }
+          match self.compare_exchange_weak(prev, next, set_order, fetch_order) {
+            Ok(prev) => return Ok(prev),
+            Err(next_prev) => prev = next_prev,
+          }
+        }
+        Err(prev)
+      }
+      /// Spins with the default `Backoff` until the loaded value equals `target`.
+      #[inline]
+      pub fn wait_until(&self, target: T, order: Ordering) {
+        self.wait_while(|current| current != target, order);
+      }
+      /// Same as `wait_until`, but gives up after `timeout`. Returns `true` if `target` was reached.
+      #[cfg(feature = "std")]
+      #[inline]
+      pub fn wait_until_timeout(&self, target: T, order: Ordering, timeout: std::time::Duration) -> bool {
+        let start = std::time::Instant::now();
+        let mut backoff = $crate::backoff::Backoff::<{ $crate::backoff::DEFAULT_SPIN_LIMIT }>::new();
+        loop {
+          if self.load(order) == target {
+            return true;
+          }
+          if start.elapsed() >= timeout {
+            return false;
+          }
+          backoff.snooze();
+        }
+      }
+    }
+    impl<T: Copy> $struct_name<T> {
+      /// Spins with the default `Backoff` while `pred` returns `true` for the loaded value. Returns the first
+      /// value for which it returned `false`.
+      #[inline]
+      pub fn wait_while(&self, mut pred: impl FnMut(T) -> bool, order: Ordering) -> T {
+        let mut backoff = $crate::backoff::Backoff::<{ $crate::backoff::DEFAULT_SPIN_LIMIT }>::new();
+        loop {
+          let current = self.load(order);
+          if !pred(current) {
+            return current;
+          }
+          backoff.snooze();
+        }
+      }
+      /// Keeps replacing the value with `f(value)` until the resulting value satisfies `cond`, and returns it.
+      /// Returns the current value right away if it already satisfies `cond`. Failed CASes snooze with the
+      /// default `Backoff`.
+      ///
+      /// Never returns unless `cond` eventually holds, so `f` must be making progress towards it (and other
+      /// threads must not keep moving the value away from it).
+      #[inline]
+      pub fn fetch_update_until(
+        &self, set_order: Ordering, fetch_order: Ordering, cond: impl Fn(T) -> bool, mut f: impl FnMut(T) -> T,
+      ) -> T {
+        let mut backoff = $crate::backoff::Backoff::<{ $crate::backoff::DEFAULT_SPIN_LIMIT }>::new();
+        let mut current = self.load(fetch_order);
+        while !cond(current) {
+          let next = f(current);
+          match self.compare_exchange_weak(current, next, set_order, fetch_order) {
+            Ok(_) => current = next,
+            Err(actual) => {
+              current = actual;
+              backoff.snooze();
+            }
+          }
+        }
+        current
+      }
+    }
+    // Integers of the same width are transmuted to and from the unsigned backing integer, which gives the same
+    // two's complement results. Undersized integers (`new_unchecked_size`) are rejected, since carries would
+    // leak into the padding bytes.
+    impl<T: AtomicInt> $struct_name<T> {
+      /// Wrapping add, returns the previous value.
+      #[inline]
+      pub fn fetch_add(&self, value: T, order: Ordering) -> T {
+        Self::assert_exact_size();
+        Self::transmute_to_t(self.data.fetch_add(Self::transmute_to_u(value), order))
+      }
+      /// Wrapping sub, returns the previous value.
+      #[inline]
+      pub fn fetch_sub(&self, value: T, order: Ordering) -> T {
+        Self::assert_exact_size();
+        Self::transmute_to_t(self.data.fetch_sub(Self::transmute_to_u(value), order))
+      }
+      /// Bitwise and, returns the previous value.
+      #[inline]
+      pub fn fetch_and(&self, value: T, order: Ordering) -> T {
+        Self::assert_exact_size();
+        Self::transmute_to_t(self.data.fetch_and(Self::transmute_to_u(value), order))
+      }
+      /// Bitwise or, returns the previous value.
+      #[inline]
+      pub fn fetch_or(&self, value: T, order: Ordering) -> T {
+        Self::assert_exact_size();
+        Self::transmute_to_t(self.data.fetch_or(Self::transmute_to_u(value), order))
+      }
+      /// Replaces the value with `(value + delta)` modulo `modulus` (`rem_euclid`, so the result is never
+      /// negative), e.g. to advance a ring buffer index. Returns the previous value.
+      ///
+      /// The sum is computed without overflow, and the new value is always in `0..|modulus|`, even if the
+      /// previous one wasn't (or `modulus` is negative). Panics if `modulus` is 0.
+      #[inline]
+      pub fn fetch_add_mod(&self, delta: T, modulus: T, set_order: Ordering, fetch_order: Ordering) -> T {
+        let modulus = modulus.to_i128();
+        assert!(modulus != 0, "modulus must be nonzero");
+        let delta = delta.to_i128();
+        let update = |value: T| Some(T::from_i128((value.to_i128() + delta).rem_euclid(modulus)));
+        match self.fetch_update(set_order, fetch_order, update) {
+          Ok(prev) | Err(prev) => prev,
+        }
+      }
+      /// Bitwise xor, returns the previous value.
+      #[inline]
+      pub fn fetch_xor(&self, value: T, order: Ordering) -> T {
+        Self::assert_exact_size();
+        Self::transmute_to_t(self.data.fetch_xor(Self::transmute_to_u(value), order))
+      }
+      /// Maximum with `value`, returns the previous value.
+      ///
+      /// For signed `T`, it's a CAS loop, since the underlying unsigned atomic would order negative values above
+      /// positive ones.
+      #[inline]
+      pub fn fetch_max(&self, value: T, order: Ordering) -> T {
+        Self::assert_exact_size();
+        if T::SIGNED {
+          let update = |prev: T| (value.to_i128() > prev.to_i128()).then_some(value);
+          match self.fetch_update(order, fetch_ordering(order), update) {
+            Ok(prev) | Err(prev) => prev,
+          }
+        } else {
+          Self::transmute_to_t(self.data.fetch_max(Self::transmute_to_u(value), order))
+        }
+      }
+      /// Minimum with `value`, returns the previous value.
+      ///
+      /// For signed `T`, it's a CAS loop, same as `fetch_max`.
+      #[inline]
+      pub fn fetch_min(&self, value: T, order: Ordering) -> T {
+        Self::assert_exact_size();
+        if T::SIGNED {
+          let update = |prev: T| (value.to_i128() < prev.to_i128()).then_some(value);
+          match self.fetch_update(order, fetch_ordering(order), update) {
+            Ok(prev) | Err(prev) => prev,
+          }
+        } else {
+          Self::transmute_to_t(self.data.fetch_min(Self::transmute_to_u(value), order))
+        }
+      }
+    }
     impl<T: Default + Copy> $struct_name<T> {
     //   fn default() -> Self { Self::new(T::default()) }
       #[inline]
@@ -155,6 +462,47 @@ macro_rules! impl_atomic_t {
       #[inline]
       fn from(value: T) -> Self { Self::new_no_uninit(value) }
     }
+    #[cfg(feature = "serde")]
+    impl<T: serde::Serialize + Copy> serde::Serialize for $struct_name<T> {
+      fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.load(Ordering::Relaxed).serialize(serializer)
+      }
+    }
+    // Only with `bytemuck` too: deserializing needs the safe `new_no_uninit` constructor, since the
+    // deserialized `T` could have padding otherwise.
+    #[cfg(all(feature = "serde", feature = "bytemuck"))]
+    impl<'a, T: serde::Deserialize<'a> + bytemuck::NoUninit> serde::Deserialize<'a> for $struct_name<T> {
+      fn deserialize<D: serde::Deserializer<'a>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Self::new_no_uninit(T::deserialize(deserializer)?))
+      }
+    }
+    )*
+  };
+}
+// Conversions to and from the `atomic_t_mu` type of the same width
+macro_rules! impl_atomic_t_mu_conversions {
+  ($($struct_name:ident),*) => {
+    $(
+    impl<T: Copy> From<$struct_name<T>> for crate::atomic_t_mu::$struct_name<T> {
+      #[inline]
+      fn from(value: $struct_name<T>) -> Self { Self::new(value.into_inner()) }
+    }
+    #[cfg(feature = "bytemuck")]
+    impl<T: bytemuck::NoUninit> From<crate::atomic_t_mu::$struct_name<T>> for $struct_name<T> {
+      #[inline]
+      fn from(value: crate::atomic_t_mu::$struct_name<T>) -> Self { Self::new_no_uninit(value.into_inner()) }
+    }
+    impl<T: Copy> $struct_name<T> {
+      /// Converts from the `atomic_t_mu` variant of the same width. Converting the other way is safe, via `From`.
+      ///
+      /// # Safety
+      /// `T` cannot have any padding bytes
+      #[inline]
+      pub unsafe fn from_mu(value: crate::atomic_t_mu::$struct_name<T>) -> Self {
+        // Safety: guaranteed by the caller
+        unsafe { Self::new(value.into_inner()) }
+      }
+    }
     )*
   };
 }
@@ -162,7 +510,483 @@ impl_atomic_t! {
   AtomicT8, AtomicU8, u8;
   AtomicT16, AtomicU16, u16;
   AtomicT32, AtomicU32, u32;
-  AtomicT64, AtomicU64, u64;
   AtomicTUsize, AtomicUsize, usize;
 }
+impl_atomic_t_mu_conversions!(AtomicT8, AtomicT16, AtomicT32, AtomicTUsize);
+// Only on targets with 64-bit atomics, unless `portable-atomic` is enabled. Use `AtomicT32` pairs, or a lock,
+// elsewhere.
+#[cfg(any(target_has_atomic = "64", feature = "portable-atomic"))]
+impl_atomic_t! {
+  AtomicT64, AtomicU64, u64;
+}
+#[cfg(target_has_atomic = "64")]
+impl_atomic_t_mu_conversions!(AtomicT64);
+// `core::sync::atomic::AtomicU128` is unstable, so 128-bit atomics come from `portable-atomic`. On targets
+// without native 128-bit atomics, `portable-atomic` falls back to a lock.
+#[cfg(feature = "portable-atomic")]
+impl_atomic_t! {
+  AtomicT128, portable_atomic::AtomicU128, u128;
+}
+
+/// `AtomicBool` with the same method signatures as the `AtomicT*` types. Unlike `AtomicT8<bool>`, it has a
+/// safe constructor and the boolean fetch ops.
+#[derive(Default)]
+pub struct AtomicTBool {
+    data: AtomicBool,
+}
+impl AtomicTBool {
+    loom_const_fn! {
+        #[inline]
+        pub fn new(value: bool) -> Self {
+            Self {
+                data: AtomicBool::new(value),
+            }
+        }
+    }
+    #[cfg(not(loom))]
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut bool {
+        self.data.get_mut()
+    }
+    #[inline]
+    pub fn load(&self, ordering: Ordering) -> bool {
+        self.data.load(ordering)
+    }
+    #[inline]
+    pub fn store(&self, value: bool, ordering: Ordering) {
+        self.data.store(value, ordering)
+    }
+    #[inline]
+    pub fn into_inner(self) -> bool {
+        self.data.into_inner()
+    }
+    #[inline]
+    pub fn swap(&self, value: bool, order: Ordering) -> bool {
+        self.data.swap(value, order)
+    }
+    #[inline]
+    pub fn compare_exchange(
+        &self,
+        current: bool,
+        new: bool,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<bool, bool> {
+        self.data.compare_exchange(current, new, success, failure)
+    }
+    #[inline]
+    pub fn compare_exchange_weak(
+        &self,
+        current: bool,
+        new: bool,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<bool, bool> {
+        self.data
+            .compare_exchange_weak(current, new, success, failure)
+    }
+    #[inline]
+    pub fn fetch_update(
+        &self,
+        set_order: Ordering,
+        fetch_order: Ordering,
+        f: impl FnMut(bool) -> Option<bool>,
+    ) -> Result<bool, bool> {
+        self.data.fetch_update(set_order, fetch_order, f)
+    }
+    #[inline]
+    pub fn fetch_and(&self, value: bool, order: Ordering) -> bool {
+        self.data.fetch_and(value, order)
+    }
+    #[inline]
+    pub fn fetch_nand(&self, value: bool, order: Ordering) -> bool {
+        self.data.fetch_nand(value, order)
+    }
+    #[inline]
+    pub fn fetch_or(&self, value: bool, order: Ordering) -> bool {
+        self.data.fetch_or(value, order)
+    }
+    #[inline]
+    pub fn fetch_xor(&self, value: bool, order: Ordering) -> bool {
+        self.data.fetch_xor(value, order)
+    }
+    #[cfg(not(loom))]
+    #[inline]
+    pub fn as_ptr(&self) -> *mut bool {
+        self.data.as_ptr()
+    }
+}
+impl fmt::Debug for AtomicTBool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.load(Ordering::Relaxed), f)
+    }
+}
+impl From<bool> for AtomicTBool {
+    #[inline]
+    fn from(value: bool) -> Self {
+        Self::new(value)
+    }
+}
+#[cfg(feature = "serde")]
+impl serde::Serialize for AtomicTBool {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.load(Ordering::Relaxed).serialize(serializer)
+    }
+}
+#[cfg(feature = "serde")]
+impl<'a> serde::Deserialize<'a> for AtomicTBool {
+    fn deserialize<D: serde::Deserializer<'a>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Self::new(bool::deserialize(deserializer)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn atomics(values: &[u32]) -> Vec<AtomicT32<u32>> {
+        // Safety: u32 has no padding
+        values
+            .iter()
+            .map(|&v| unsafe { AtomicT32::new(v) })
+            .collect()
+    }
+
+    #[test]
+    fn compare_exchange_all_counts_matches() {
+        let slots = atomics(&[1, 0, 1, 2, 1]);
+        let flipped =
+            AtomicT32::compare_exchange_all(&slots, 1, 0, Ordering::AcqRel, Ordering::Relaxed);
+        assert_eq!(flipped, 3);
+        let values: Vec<u32> = slots.iter().map(|a| a.load(Ordering::Relaxed)).collect();
+        assert_eq!(values, [0, 0, 0, 2, 0]);
+        assert_eq!(
+            AtomicT32::compare_exchange_all(&slots, 1, 0, Ordering::AcqRel, Ordering::Relaxed),
+            0
+        );
+    }
+
+    #[test]
+    fn fetch_update_if_changed_skips_equal_store() {
+        // Safety: f32 has no padding
+        let value = unsafe { AtomicT32::new(0.0f32) };
+        // -0.0 == 0.0, so the store is skipped, and the bits stay those of 0.0
+        let result =
+            value.fetch_update_if_changed(Ordering::AcqRel, Ordering::Acquire, |_| Some(-0.0));
+        assert_eq!(result.map(f32::to_bits), Ok(0.0f32.to_bits()));
+        assert_eq!(value.load(Ordering::Relaxed).to_bits(), 0.0f32.to_bits());
+
+        let result =
+            value.fetch_update_if_changed(Ordering::AcqRel, Ordering::Acquire, |v| Some(v + 1.0));
+        assert_eq!(result, Ok(0.0));
+        assert_eq!(value.load(Ordering::Relaxed), 1.0);
+
+        let result = value.fetch_update_if_changed(Ordering::AcqRel, Ordering::Acquire, |_| None);
+        assert_eq!(result, Err(1.0));
+    }
+
+    #[test]
+    fn mu_round_trip() {
+        // Safety: u32 has no padding
+        let value = unsafe { AtomicT32::new(7u32) };
+        let mu: crate::atomic_t_mu::AtomicT32<u32> = value.into();
+        assert_eq!(mu.load(Ordering::Relaxed), 7);
+        // Safety: u32 has no padding
+        let value = unsafe { AtomicT32::from_mu(mu) };
+        assert_eq!(value.into_inner(), 7);
+    }
 
+    #[cfg(feature = "bytemuck")]
+    #[test]
+    fn mu_round_trip_no_uninit() {
+        let mu = crate::atomic_t_mu::AtomicT16::new(3u16);
+        let value: AtomicT16<u16> = mu.into();
+        let mu: crate::atomic_t_mu::AtomicT16<u16> = value.into();
+        assert_eq!(mu.into_inner(), 3);
+    }
+
+    #[test]
+    fn wait_until_other_thread_stores() {
+        // Safety: u8 has no padding
+        let state = unsafe { AtomicT8::new(0u8) };
+        std::thread::scope(|s| {
+            let until = s.spawn(|| state.wait_until(2, Ordering::Acquire));
+            let until_above = s.spawn(|| state.wait_while(|v| v < 2, Ordering::Acquire));
+            state.store(1, Ordering::Release);
+            state.store(2, Ordering::Release);
+            until.join().unwrap();
+            assert_eq!(until_above.join().unwrap(), 2);
+        });
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn wait_until_timeout() {
+        // Safety: u8 has no padding
+        let state = unsafe { AtomicT8::new(0u8) };
+        assert!(!state.wait_until_timeout(
+            1,
+            Ordering::Acquire,
+            std::time::Duration::from_millis(5)
+        ));
+        state.store(1, Ordering::Release);
+        assert!(state.wait_until_timeout(
+            1,
+            Ordering::Acquire,
+            std::time::Duration::from_millis(5)
+        ));
+    }
+
+    #[cfg(any(target_has_atomic = "64", feature = "portable-atomic"))]
+    #[test]
+    fn atomic_t64_where_available() {
+        // Safety: u64 has no padding
+        let value = unsafe { AtomicT64::new(u64::MAX - 1) };
+        assert_eq!(value.swap(u64::MAX, Ordering::AcqRel), u64::MAX - 1);
+        assert_eq!(value.load(Ordering::Acquire), u64::MAX);
+    }
+
+    #[cfg(not(loom))] // `loom` atomics have no `as_ptr`
+    #[test]
+    fn undersized_through_unchecked_size() {
+        // Safety: u16 has no padding, and nothing accesses the container directly
+        let a = unsafe { AtomicT32::<u16>::new_unchecked_size(0x1234) };
+        assert_eq!(a.load(Ordering::Relaxed), 0x1234);
+        assert_eq!(a.swap(0xffff, Ordering::Relaxed), 0x1234);
+        assert_eq!(
+            a.compare_exchange(0xffff, 7, Ordering::Relaxed, Ordering::Relaxed),
+            Ok(0xffff)
+        );
+        assert_eq!(
+            a.compare_exchange(0xffff, 8, Ordering::Relaxed, Ordering::Relaxed),
+            Err(7)
+        );
+        // Trailing bytes stay zeroed.
+        let [lo, hi] = 7u16.to_ne_bytes();
+        // Safety: the container is a valid, initialized u32
+        let container = unsafe { a.as_ptr().cast::<u32>().read() };
+        assert_eq!(container, u32::from_ne_bytes([lo, hi, 0, 0]));
+    }
+
+    #[test]
+    fn integer_fetch_ops_wrap() {
+        // Safety: i32 has no padding
+        let a = unsafe { AtomicT32::new(i32::MAX) };
+        assert_eq!(a.fetch_add(1, Ordering::Relaxed), i32::MAX);
+        assert_eq!(a.load(Ordering::Relaxed), i32::MIN);
+        assert_eq!(a.fetch_sub(1, Ordering::Relaxed), i32::MIN);
+        assert_eq!(a.load(Ordering::Relaxed), i32::MAX);
+        a.store(-1, Ordering::Relaxed);
+        assert_eq!(a.fetch_add(2, Ordering::Relaxed), -1);
+        assert_eq!(a.load(Ordering::Relaxed), 1);
+
+        assert_eq!(a.fetch_or(0b110, Ordering::Relaxed), 0b001);
+        assert_eq!(a.fetch_and(0b011, Ordering::Relaxed), 0b111);
+        assert_eq!(a.fetch_xor(-1, Ordering::Relaxed), 0b011);
+        assert_eq!(a.load(Ordering::Relaxed), !0b011);
+    }
+
+    #[test]
+    fn bool_fetch_nand_truth_table() {
+        for (a, b) in [(false, false), (false, true), (true, false), (true, true)] {
+            let atomic = AtomicTBool::new(a);
+            assert_eq!(atomic.fetch_nand(b, Ordering::Relaxed), a);
+            assert_eq!(atomic.load(Ordering::Relaxed), !(a && b), "{a} nand {b}");
+        }
+    }
+
+    #[test]
+    fn bool_ops() {
+        let atomic = AtomicTBool::new(false);
+        assert!(!atomic.swap(true, Ordering::Relaxed));
+        assert_eq!(
+            atomic.compare_exchange(true, false, Ordering::Relaxed, Ordering::Relaxed),
+            Ok(true)
+        );
+        assert_eq!(
+            atomic.compare_exchange(true, false, Ordering::Relaxed, Ordering::Relaxed),
+            Err(false)
+        );
+        assert!(!atomic.fetch_or(true, Ordering::Relaxed));
+        assert!(atomic.fetch_and(false, Ordering::Relaxed));
+        assert!(!atomic.fetch_xor(true, Ordering::Relaxed));
+        assert!(atomic.into_inner());
+    }
+
+    #[test]
+    fn fetch_add_mod_wraparound() {
+        // Safety: integers have no padding
+        let index = unsafe { AtomicT32::new(5u32) };
+        assert_eq!(
+            index.fetch_add_mod(3, 7, Ordering::Relaxed, Ordering::Relaxed),
+            5
+        );
+        assert_eq!(index.load(Ordering::Relaxed), 1);
+        // Out of range previous value, and a negative delta or modulus, still land in `0..|modulus|`.
+        index.store(100, Ordering::Relaxed);
+        index.fetch_add_mod(0, 7, Ordering::Relaxed, Ordering::Relaxed);
+        assert_eq!(index.load(Ordering::Relaxed), 2);
+        // Safety: integers have no padding
+        let signed = unsafe { AtomicT8::new(0i8) };
+        signed.fetch_add_mod(-1, 5, Ordering::Relaxed, Ordering::Relaxed);
+        assert_eq!(signed.load(Ordering::Relaxed), 4);
+        signed.fetch_add_mod(i8::MAX, i8::MIN, Ordering::Relaxed, Ordering::Relaxed);
+        assert_eq!(signed.load(Ordering::Relaxed), 3);
+
+        index.store(0, Ordering::Relaxed);
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    for _ in 0..250 {
+                        let prev = index.fetch_add_mod(1, 7, Ordering::Relaxed, Ordering::Relaxed);
+                        assert!(prev < 7);
+                    }
+                });
+            }
+        });
+        assert_eq!(index.load(Ordering::Relaxed), 1000 % 7);
+    }
+
+    #[test]
+    #[should_panic(expected = "modulus must be nonzero")]
+    fn fetch_add_mod_zero_modulus() {
+        // Safety: integers have no padding
+        let index = unsafe { AtomicT32::new(0u32) };
+        index.fetch_add_mod(1, 0, Ordering::Relaxed, Ordering::Relaxed);
+    }
+
+    #[cfg(feature = "portable-atomic")]
+    #[test]
+    fn atomic_t128_pair_compare_exchange() {
+        #[repr(C)]
+        #[derive(Clone, Copy, PartialEq, Debug)]
+        struct Pair(u64, u64);
+        // Safety: two u64s, no padding
+        let pair = unsafe { AtomicT128::new(Pair(1, 2)) };
+        assert_eq!(
+            pair.compare_exchange(Pair(1, 2), Pair(3, 4), Ordering::AcqRel, Ordering::Acquire),
+            Ok(Pair(1, 2))
+        );
+        assert_eq!(
+            pair.compare_exchange(Pair(1, 2), Pair(5, 6), Ordering::AcqRel, Ordering::Acquire),
+            Err(Pair(3, 4))
+        );
+        assert_eq!(pair.load(Ordering::Acquire), Pair(3, 4));
+    }
+
+    /// Runs with and without `portable-atomic`: coercing the methods to fn pointers pins their signatures, so
+    /// the backend can't leak into the API.
+    #[cfg(not(loom))] // `loom` atomics have no `as_ptr`
+    #[test]
+    fn api_is_backend_independent() {
+        type A = AtomicT32<u32>;
+        let load: fn(&A, Ordering) -> u32 = A::load;
+        let store: fn(&A, u32, Ordering) = A::store;
+        let swap: fn(&A, u32, Ordering) -> u32 = A::swap;
+        let compare_exchange: fn(&A, u32, u32, Ordering, Ordering) -> Result<u32, u32> =
+            A::compare_exchange;
+        let fetch_add: fn(&A, u32, Ordering) -> u32 = A::fetch_add;
+        let as_ptr: fn(&A) -> *mut u32 = A::as_ptr;
+        let bool_fetch_nand: fn(&AtomicTBool, bool, Ordering) -> bool = AtomicTBool::fetch_nand;
+
+        // Safety: u32 has no padding
+        let a = unsafe { A::new(1) };
+        store(&a, 2, Ordering::Relaxed);
+        assert_eq!(swap(&a, 3, Ordering::Relaxed), 2);
+        assert_eq!(
+            compare_exchange(&a, 3, 4, Ordering::Relaxed, Ordering::Relaxed),
+            Ok(3)
+        );
+        assert_eq!(fetch_add(&a, 1, Ordering::Relaxed), 4);
+        assert_eq!(load(&a, Ordering::Relaxed), 5);
+        assert!(!as_ptr(&a).is_null());
+        assert!(bool_fetch_nand(
+            &AtomicTBool::new(true),
+            true,
+            Ordering::Relaxed
+        ));
+    }
+
+    #[test]
+    fn integer_fetch_min_max_signedness() {
+        // Safety: integers have no padding
+        let signed = unsafe { AtomicT32::new(3i32) };
+        assert_eq!(signed.fetch_min(-5, Ordering::Relaxed), 3);
+        assert_eq!(signed.load(Ordering::Relaxed), -5);
+        assert_eq!(signed.fetch_min(0, Ordering::Relaxed), -5);
+        assert_eq!(signed.fetch_max(-7, Ordering::Relaxed), -5);
+        assert_eq!(signed.load(Ordering::Relaxed), -5);
+        assert_eq!(signed.fetch_max(2, Ordering::Relaxed), -5);
+        assert_eq!(signed.load(Ordering::Relaxed), 2);
+
+        // Safety: integers have no padding
+        let depth = unsafe { AtomicT32::new(0u32) };
+        std::thread::scope(|s| {
+            for t in 0..4 {
+                let depth = &depth;
+                s.spawn(move || {
+                    for i in 0..100 {
+                        depth.fetch_max(t * 100 + i, Ordering::Relaxed);
+                    }
+                });
+            }
+        });
+        assert_eq!(depth.load(Ordering::Relaxed), 399);
+        assert_eq!(depth.fetch_min(u32::MAX, Ordering::Relaxed), 399);
+        assert_eq!(depth.load(Ordering::Relaxed), 399);
+    }
+
+    #[test]
+    fn fetch_update_until_reaches_target() {
+        let counter = AtomicT32::<u32>::new_safe(3);
+        let mut calls = 0;
+        let reached = counter.fetch_update_until(
+            Ordering::AcqRel,
+            Ordering::Acquire,
+            |v| v >= 10,
+            |v| {
+                calls += 1;
+                v + 1
+            },
+        );
+        assert_eq!((reached, calls), (10, 7));
+        assert_eq!(counter.load(Ordering::Relaxed), 10);
+
+        // Already meets `cond`: returned as is, without calling `f`
+        let reached = counter.fetch_update_until(
+            Ordering::AcqRel,
+            Ordering::Acquire,
+            |v| v >= 5,
+            |_| unreachable!(),
+        );
+        assert_eq!(reached, 10);
+        assert_eq!(counter.load(Ordering::Relaxed), 10);
+
+        // Concurrent advances stop exactly at the target
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    let reached = counter.fetch_update_until(
+                        Ordering::AcqRel,
+                        Ordering::Acquire,
+                        |v| v >= 1000,
+                        |v| v + 1,
+                    );
+                    assert_eq!(reached, 1000);
+                });
+            }
+        });
+        assert_eq!(counter.load(Ordering::Relaxed), 1000);
+    }
+
+    #[cfg(all(feature = "serde", feature = "bytemuck"))]
+    #[test]
+    fn serde_json_round_trip() {
+        let value = AtomicT32::<u32>::new_no_uninit(0xdead_beef);
+        let json = serde_json::to_string(&value).unwrap();
+        assert_eq!(json, "3735928559");
+        let back: AtomicT32<u32> = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.load(Ordering::Relaxed), 0xdead_beef);
+        assert!(serde_json::from_str::<AtomicT32<u32>>("-1").is_err());
+    }
+}
@@ -1,4 +1,4 @@
-use core::{fmt, marker::PhantomData, mem, mem::MaybeUninit, sync::atomic::Ordering};
+use core::{fmt, marker::PhantomData, mem, mem::MaybeUninit, ptr, sync::atomic::Ordering};
 
 use atomic_maybe_uninit::AtomicMaybeUninit;
 
@@ -14,8 +14,8 @@ macro_rules! impl_atomic_t {
       const fn transmute_to_t(value: MaybeUninit<$int>) -> T {
         $crate::const_type_assert!(
           T,
-          mem::size_of::<T>() == mem::size_of::<$int>(),
-          "Size of T must be same as in the name of the container",
+          mem::size_of::<T>() <= mem::size_of::<$int>(),
+          "Size of T must be <= than in the name of the container",
         );
         $crate::const_type_assert!(
           T,
@@ -25,13 +25,13 @@ macro_rules! impl_atomic_t {
 
         // Safety:
         // As long as `value` was produced by transmuting T -> $int, inverse direction is OK
-        unsafe { mem::transmute_copy(&value.assume_init()) }
+        unsafe { ptr::read(value.as_ptr().cast::<T>()) }
       }
       const fn transmute_to_u(value: T) -> MaybeUninit<$int> {
         $crate::const_type_assert!(
           T,
-          mem::size_of::<T>() == mem::size_of::<$int>(),
-          "Size of T must be same as in the name of the container",
+          mem::size_of::<T>() <= mem::size_of::<$int>(),
+          "Size of T must be <= than in the name of the container",
         );
         $crate::const_type_assert!(
           T,
@@ -39,14 +39,39 @@ macro_rules! impl_atomic_t {
           "Align of T must be <= than the name of the container",
         );
 
+        let mut data = MaybeUninit::<$int>::zeroed();
         // Safety:
-        // Transmuting to MaybeUninit<$int> of same size and alignment is safe
-        unsafe { mem::transmute_copy(&value) }
+        // T fits into MaybeUninit<$int>. If T is smaller, the remaining bytes stay zeroed.
+        unsafe {
+          ptr::copy_nonoverlapping(ptr::from_ref(&value).cast::<u8>(), data.as_mut_ptr().cast::<u8>(), mem::size_of::<T>())
+        };
+        data
+      }
+      const fn assert_exact_size() {
+        $crate::const_type_assert!(
+          T,
+          mem::size_of::<T>() == mem::size_of::<$int>(),
+          "Size of T must be same as in the name of the container",
+        );
       }
 
 
       #[inline]
       pub const fn new(value: T) -> Self {
+        Self::assert_exact_size();
+        // Safety: size of T matches the container
+        unsafe { Self::new_unchecked_size(value) }
+      }
+      /// Like `new`, but `T` may be smaller than the container. The value is stored in the low-address bytes
+      /// and the remaining bytes are zeroed. `T` that is bigger or more aligned than the container still
+      /// fails to compile.
+      ///
+      /// # Safety
+      /// Anything that accesses the whole container through `as_ptr` (e.g. over FFI) must keep the trailing
+      /// bytes zeroed. Otherwise the comparisons in `compare_exchange` and friends spuriously fail. The integer
+      /// value seen that way depends on endianness.
+      #[inline]
+      pub const unsafe fn new_unchecked_size(value: T) -> Self {
         let data = Self::transmute_to_u(value);
         Self { data: <$atomic>::new(data), _pd: PhantomData }
       }
@@ -105,8 +130,113 @@ macro_rules! impl_atomic_t {
       }
       #[inline]
       pub fn as_ptr(&self) -> *mut T { self.data.as_ptr() as *mut T }
+
+      /// Tries to `compare_exchange` every element from `current` to `new`, returning the number of elements
+      /// that were swapped. Each element is exchanged independently, this is not a transaction.
+      #[inline]
+      pub fn compare_exchange_all(
+        slice: &[Self], current: T, new: T, success: Ordering, failure: Ordering,
+      ) -> usize {
+        slice.iter().filter(|a| a.compare_exchange(current, new, success, failure).is_ok()).count()
+      }
+      /// Stores every element of `src` into the corresponding element of `dst`, with `Relaxed` ordering.
+      ///
+      /// # Panics
+      /// If the lengths of `dst` and `src` differ.
+      #[inline]
+      pub fn write_slice(dst: &[Self], src: &[T]) {
+        assert_eq!(dst.len(), src.len(), "Length of dst must be same as length of src");
+        for (a, &v) in dst.iter().zip(src) {
+          a.store(v, Ordering::Relaxed);
+        }
+      }
+      #[cfg(feature = "alloc")]
+      #[inline]
+      pub fn from_slice(src: &[T]) -> alloc::vec::Vec<Self> {
+        src.iter().map(|&v| Self::new(v)).collect()
+      }
     }
 
+    impl<T: PartialEq + Copy> $struct_name<T> {
+      /// Same as `fetch_update`, but when `f` returns a value equal (`PartialEq`) to the current one, the store
+      /// is skipped, so the cache line is not dirtied by no-op updates.
+      #[inline]
+      pub fn fetch_update_if_changed(
+        &self, set_order: Ordering, fetch_order: Ordering, mut f: impl FnMut(T) -> Option<T>,
+      ) -> Result<T, T> {
+        let mut prev = self.load(fetch_order);
+        while let Some(next) = f(prev) {
+          if next == prev {
+            return Ok(prev);
+          }
+          match self.compare_exchange_weak(prev, next, set_order, fetch_order) {
+            Ok(prev) => return Ok(prev),
+            Err(next_prev) => prev = next_prev,
+          }
+        }
+        Err(prev)
+      }
+      /// Spins with the default `Backoff` until the loaded value equals `target`.
+      #[inline]
+      pub fn wait_until(&self, target: T, order: Ordering) {
+        self.wait_while(|current| current != target, order);
+      }
+      /// Same as `wait_until`, but gives up after `timeout`. Returns `true` if `target` was reached.
+      #[cfg(feature = "std")]
+      #[inline]
+      pub fn wait_until_timeout(&self, target: T, order: Ordering, timeout: std::time::Duration) -> bool {
+        let start = std::time::Instant::now();
+        let mut backoff = $crate::backoff::Backoff::<{ $crate::backoff::DEFAULT_SPIN_LIMIT }>::new();
+        loop {
+          if self.load(order) == target {
+            return true;
+          }
+          if start.elapsed() >= timeout {
+            return false;
+          }
+          backoff.snooze();
+        }
+      }
+    }
+    impl<T: Copy> $struct_name<T> {
+      /// Spins with the default `Backoff` while `pred` returns `true` for the loaded value. Returns the first
+      /// value for which it returned `false`.
+      #[inline]
+      pub fn wait_while(&self, mut pred: impl FnMut(T) -> bool, order: Ordering) -> T {
+        let mut backoff = $crate::backoff::Backoff::<{ $crate::backoff::DEFAULT_SPIN_LIMIT }>::new();
+        loop {
+          let current = self.load(order);
+          if !pred(current) {
+            return current;
+          }
+          backoff.snooze();
+        }
+      }
+      /// Keeps replacing the value with `f(value)` until the resulting value satisfies `cond`, and returns it.
+      /// Returns the current value right away if it already satisfies `cond`. Failed CASes snooze with the
+      /// default `Backoff`.
+      ///
+      /// Never returns unless `cond` eventually holds, so `f` must be making progress towards it (and other
+      /// threads must not keep moving the value away from it).
+      #[inline]
+      pub fn fetch_update_until(
+        &self, set_order: Ordering, fetch_order: Ordering, cond: impl Fn(T) -> bool, mut f: impl FnMut(T) -> T,
+      ) -> T {
+        let mut backoff = $crate::backoff::Backoff::<{ $crate::backoff::DEFAULT_SPIN_LIMIT }>::new();
+        let mut current = self.load(fetch_order);
+        while !cond(current) {
+          let next = f(current);
+          match self.compare_exchange_weak(current, next, set_order, fetch_order) {
+            Ok(_) => current = next,
+            Err(actual) => {
+              current = actual;
+              backoff.snooze();
+            }
+          }
+        }
+        current
+      }
+    }
     impl<T: Default + Copy> $struct_name<T> {
     //   fn default() -> Self { Self::new(T::default()) }
       #[inline]
@@ -138,13 +268,164 @@ macro_rules! impl_atomic_t {
 type AMUu8 = AtomicMaybeUninit<u8>;
 type AMUu16 = AtomicMaybeUninit<u16>;
 type AMUu32 = AtomicMaybeUninit<u32>;
+#[cfg(target_has_atomic = "64")]
 type AMUu64 = AtomicMaybeUninit<u64>;
 type AMUusize = AtomicMaybeUninit<usize>;
 impl_atomic_t! {
   AtomicT8, AMUu8, u8;
   AtomicT16, AMUu16, u16;
   AtomicT32, AMUu32, u32;
-  AtomicT64, AMUu64, u64;
   AtomicTUsize, AMUusize, usize;
 }
+// Only on targets with 64-bit atomics. Use `AtomicT32` pairs, or a lock, elsewhere.
+#[cfg(target_has_atomic = "64")]
+impl_atomic_t! {
+  AtomicT64, AMUu64, u64;
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compare_exchange_all_counts_matches() {
+        let slots: Vec<AtomicT32<[u16; 2]>> = [[1, 1], [0, 0], [1, 1], [1, 2]]
+            .into_iter()
+            .map(AtomicT32::new)
+            .collect();
+        let flipped = AtomicT32::compare_exchange_all(
+            &slots,
+            [1, 1],
+            [0, 0],
+            Ordering::AcqRel,
+            Ordering::Relaxed,
+        );
+        assert_eq!(flipped, 2);
+        let values: Vec<[u16; 2]> = slots.iter().map(|a| a.load(Ordering::Relaxed)).collect();
+        assert_eq!(values, [[0, 0], [0, 0], [0, 0], [1, 2]]);
+    }
+
+    #[test]
+    fn fetch_update_if_changed_skips_equal_store() {
+        let value = AtomicT32::new(0.0f32);
+        let result =
+            value.fetch_update_if_changed(Ordering::AcqRel, Ordering::Acquire, |_| Some(-0.0));
+        assert_eq!(result.map(f32::to_bits), Ok(0.0f32.to_bits()));
+        assert_eq!(value.load(Ordering::Relaxed).to_bits(), 0.0f32.to_bits());
+
+        let result =
+            value.fetch_update_if_changed(Ordering::AcqRel, Ordering::Acquire, |v| Some(v + 1.0));
+        assert_eq!(result, Ok(0.0));
+        assert_eq!(value.load(Ordering::Relaxed), 1.0);
+    }
+
+    #[test]
+    fn wait_until_other_thread_stores() {
+        let state = AtomicT8::new(0u8);
+        std::thread::scope(|s| {
+            let until = s.spawn(|| state.wait_until(2, Ordering::Acquire));
+            let until_above = s.spawn(|| state.wait_while(|v| v < 2, Ordering::Acquire));
+            state.store(1, Ordering::Release);
+            state.store(2, Ordering::Release);
+            until.join().unwrap();
+            assert_eq!(until_above.join().unwrap(), 2);
+        });
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn wait_until_timeout() {
+        let state = AtomicT8::new(0u8);
+        assert!(!state.wait_until_timeout(
+            1,
+            Ordering::Acquire,
+            std::time::Duration::from_millis(5)
+        ));
+        state.store(1, Ordering::Release);
+        assert!(state.wait_until_timeout(
+            1,
+            Ordering::Acquire,
+            std::time::Duration::from_millis(5)
+        ));
+    }
+
+    #[test]
+    fn write_slice_matching_lengths() {
+        let slots: Vec<AtomicT32<[u16; 2]>> = (0..3).map(|_| AtomicT32::new([0, 0])).collect();
+        AtomicT32::write_slice(&slots, &[[1, 2], [3, 4], [5, 6]]);
+        let loaded: Vec<_> = slots.iter().map(|a| a.load(Ordering::Relaxed)).collect();
+        assert_eq!(loaded, [[1, 2], [3, 4], [5, 6]]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Length of dst must be same as length of src")]
+    fn write_slice_mismatched_lengths() {
+        let slots: Vec<AtomicT32<[u16; 2]>> = (0..3).map(|_| AtomicT32::new([0, 0])).collect();
+        AtomicT32::write_slice(&slots, &[[1, 2]]);
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn from_slice_round_trip() {
+        let slots = AtomicT16::from_slice(&[[1u8, 2], [3, 4]]);
+        assert_eq!(slots.len(), 2);
+        assert_eq!(slots[1].load(Ordering::Relaxed), [3, 4]);
+    }
+
+    #[test]
+    fn undersized_through_unchecked_size() {
+        // Safety: `[u8; 3]` has no padding, and nothing accesses the container directly
+        let a = unsafe { AtomicT32::<[u8; 3]>::new_unchecked_size([1, 2, 3]) };
+        assert_eq!(a.load(Ordering::Relaxed), [1, 2, 3]);
+        assert_eq!(a.swap([4, 5, 6], Ordering::Relaxed), [1, 2, 3]);
+        assert_eq!(
+            a.compare_exchange([4, 5, 6], [7, 8, 9], Ordering::Relaxed, Ordering::Relaxed),
+            Ok([4, 5, 6])
+        );
+        assert_eq!(a.load(Ordering::Relaxed), [7, 8, 9]);
+    }
+
+    #[test]
+    fn fetch_update_until_reaches_target() {
+        let counter = AtomicT32::<u32>::new(3);
+        let mut calls = 0;
+        let reached = counter.fetch_update_until(
+            Ordering::AcqRel,
+            Ordering::Acquire,
+            |v| v >= 10,
+            |v| {
+                calls += 1;
+                v + 1
+            },
+        );
+        assert_eq!((reached, calls), (10, 7));
+        assert_eq!(counter.load(Ordering::Relaxed), 10);
 
+        // Already meets `cond`: returned as is, without calling `f`
+        let reached = counter.fetch_update_until(
+            Ordering::AcqRel,
+            Ordering::Acquire,
+            |v| v >= 5,
+            |_| unreachable!(),
+        );
+        assert_eq!(reached, 10);
+        assert_eq!(counter.load(Ordering::Relaxed), 10);
+
+        // Concurrent advances stop exactly at the target
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    let reached = counter.fetch_update_until(
+                        Ordering::AcqRel,
+                        Ordering::Acquire,
+                        |v| v >= 1000,
+                        |v| v + 1,
+                    );
+                    assert_eq!(reached, 1000);
+                });
+            }
+        });
+        assert_eq!(counter.load(Ordering::Relaxed), 1000);
+    }
+}
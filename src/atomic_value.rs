@@ -0,0 +1,85 @@
+//! Width-named aliases that pick between `atomic_t` and `atomic_t_mu` with a const flag.
+//!
+//! By default (`NO_PADDING = false`) the aliases resolve to `atomic_t_mu`, which is safe for any `Copy` type,
+//! including ones with padding bytes, but goes through inline assembly (so no `miri`, and no fusing of the
+//! atomic ops by the compiler).
+//!
+//! With `NO_PADDING = true` they resolve to `atomic_t`, which uses plain `core` atomics, but its constructor
+//! is `unsafe` (or requires `bytemuck::NoUninit` for `new_no_uninit`), since `T` must not contain any
+//! uninit bytes.
+//!
+//! Since the aliases go through an associated type, spell out `T` when calling associated functions:
+//! `AtomicValue32::<MyType>::new(v)`.
+use crate::{atomic_t, atomic_t_mu};
+
+pub struct Backend<const NO_PADDING: bool>;
+
+pub trait AtomicBackend {
+    type A8<T: Copy>;
+    type A16<T: Copy>;
+    type A32<T: Copy>;
+    #[cfg(target_has_atomic = "64")]
+    type A64<T: Copy>;
+    type AUsize<T: Copy>;
+}
+impl AtomicBackend for Backend<false> {
+    type A8<T: Copy> = atomic_t_mu::AtomicT8<T>;
+    type A16<T: Copy> = atomic_t_mu::AtomicT16<T>;
+    type A32<T: Copy> = atomic_t_mu::AtomicT32<T>;
+    #[cfg(target_has_atomic = "64")]
+    type A64<T: Copy> = atomic_t_mu::AtomicT64<T>;
+    type AUsize<T: Copy> = atomic_t_mu::AtomicTUsize<T>;
+}
+impl AtomicBackend for Backend<true> {
+    type A8<T: Copy> = atomic_t::AtomicT8<T>;
+    type A16<T: Copy> = atomic_t::AtomicT16<T>;
+    type A32<T: Copy> = atomic_t::AtomicT32<T>;
+    #[cfg(target_has_atomic = "64")]
+    type A64<T: Copy> = atomic_t::AtomicT64<T>;
+    type AUsize<T: Copy> = atomic_t::AtomicTUsize<T>;
+}
+
+pub type AtomicValue8<T, const NO_PADDING: bool = false> =
+    <Backend<NO_PADDING> as AtomicBackend>::A8<T>;
+pub type AtomicValue16<T, const NO_PADDING: bool = false> =
+    <Backend<NO_PADDING> as AtomicBackend>::A16<T>;
+pub type AtomicValue32<T, const NO_PADDING: bool = false> =
+    <Backend<NO_PADDING> as AtomicBackend>::A32<T>;
+#[cfg(target_has_atomic = "64")]
+pub type AtomicValue64<T, const NO_PADDING: bool = false> =
+    <Backend<NO_PADDING> as AtomicBackend>::A64<T>;
+pub type AtomicValueUsize<T, const NO_PADDING: bool = false> =
+    <Backend<NO_PADDING> as AtomicBackend>::AUsize<T>;
+
+#[cfg(test)]
+mod tests {
+    use core::{any::TypeId, sync::atomic::Ordering};
+
+    use super::*;
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    #[repr(C)]
+    struct Padded {
+        a: u8,
+        b: u16,
+    }
+
+    #[test]
+    fn padded_struct_defaults_to_mu() {
+        assert_eq!(
+            TypeId::of::<AtomicValue32<Padded>>(),
+            TypeId::of::<atomic_t_mu::AtomicT32<Padded>>()
+        );
+        let value = AtomicValue32::<Padded>::new(Padded { a: 1, b: 2 });
+        value.store(Padded { a: 3, b: 4 }, Ordering::Relaxed);
+        assert_eq!(value.load(Ordering::Relaxed), Padded { a: 3, b: 4 });
+    }
+
+    #[test]
+    fn no_padding_flag_selects_atomic_t() {
+        assert_eq!(
+            TypeId::of::<AtomicValue32<u32, true>>(),
+            TypeId::of::<atomic_t::AtomicT32<u32>>()
+        );
+    }
+}
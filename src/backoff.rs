@@ -1,5 +1,5 @@
 /// Basic exponential backoff implementaiton.
-
+///
 /// - If its generic param is 0, it will always execute `thread::yield_now()`.
 /// - If its generic params is positive, it will execute a number of `hint::spin_loop()` before it starts to `thread::yield_now()`.
 /// - If its generic param is negative, it will just execute `hint::spin_loop()` without ever yielding.
@@ -7,7 +7,73 @@ pub struct Backoff<const SPIN_LIMIT: isize> {
     step: usize,
 }
 pub(crate) const DEFAULT_SPIN_LIMIT: isize = 6;
+
+#[cfg(feature = "spin-hook")]
+static SPIN_HOOK: core::sync::atomic::AtomicPtr<()> =
+    core::sync::atomic::AtomicPtr::new(core::ptr::null_mut());
+
+/// Replaces `core::hint::spin_loop()` in all the backoff types with `hook`.
+///
+/// Useful on targets where `spin_loop()` is a no-op, so the hook can execute a target-specific relax
+/// instruction instead.
+#[cfg(feature = "spin-hook")]
+pub fn set_spin_hook(hook: fn()) {
+    SPIN_HOOK.store(hook as *mut (), core::sync::atomic::Ordering::Release);
+}
+#[inline(always)]
+fn spin_hint() {
+    #[cfg(loom)]
+    crate::sync::yield_now();
+    #[cfg(not(loom))]
+    {
+        #[cfg(feature = "spin-hook")]
+        {
+            let hook = SPIN_HOOK.load(core::sync::atomic::Ordering::Acquire);
+            if !hook.is_null() {
+                // Safety: only `fn()` pointers are stored in `SPIN_HOOK`
+                let hook: fn() = unsafe { core::mem::transmute::<*mut (), fn()>(hook) };
+                hook();
+                return;
+            }
+        }
+        core::hint::spin_loop();
+    }
+}
+#[cfg(feature = "wait-hook")]
+static WAIT_HOOK: core::sync::atomic::AtomicPtr<()> =
+    core::sync::atomic::AtomicPtr::new(core::ptr::null_mut());
+
+/// Without `std`, makes the backoff types call `hook` once they are past the spinning phase (where they would
+/// yield the thread with `std`), e.g. to execute `WFE` on single-core ARM targets.
+///
+/// Until a hook is registered, they keep spinning. With `std`, the hook is never called.
+#[cfg(feature = "wait-hook")]
+pub fn set_wait_hook(hook: fn()) {
+    WAIT_HOOK.store(hook as *mut (), core::sync::atomic::Ordering::Release);
+}
+/// Calls the wait hook, if one is registered.
+#[cfg(not(feature = "std"))]
+#[inline(always)]
+fn wait_hint() -> bool {
+    #[cfg(feature = "wait-hook")]
+    {
+        let hook = WAIT_HOOK.load(core::sync::atomic::Ordering::Acquire);
+        if !hook.is_null() {
+            // Safety: only `fn()` pointers are stored in `WAIT_HOOK`
+            let hook: fn() = unsafe { core::mem::transmute::<*mut (), fn()>(hook) };
+            hook();
+            return true;
+        }
+    }
+    false
+}
 // const SPIN_LIMIT: u32 = 6;
+impl<const SPIN_LIMIT: isize> Default for Backoff<SPIN_LIMIT> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
 impl<const SPIN_LIMIT: isize> Backoff<SPIN_LIMIT> {
     #[inline]
     pub fn new() -> Self {
@@ -15,9 +81,16 @@ impl<const SPIN_LIMIT: isize> Backoff<SPIN_LIMIT> {
     }
     #[inline]
     pub fn snooze(&mut self) {
+        self.snooze_proportional(1);
+    }
+    /// Same as `snooze`, but multiplies the number of spins by `contention` (e.g. number of competing threads),
+    /// so highly contended locks back off more aggressively. `contention` of 0 is treated as 1.
+    #[inline]
+    pub fn snooze_proportional(&mut self, contention: usize) {
+        let spins = self.spins(contention);
         if SPIN_LIMIT < 0 {
-            for _ in 0..1 << (-SPIN_LIMIT - 1) {
-                core::hint::spin_loop();
+            for _ in 0..spins {
+                spin_hint();
             }
             return;
         }
@@ -25,17 +98,104 @@ impl<const SPIN_LIMIT: isize> Backoff<SPIN_LIMIT> {
         #[cfg(feature = "std")]
         {
             if self.step <= SPIN_LIMIT as usize {
-                for _ in 0..1 << self.step {
-                    core::hint::spin_loop();
+                for _ in 0..spins {
+                    spin_hint();
                 }
             } else {
-                std::thread::yield_now();
+                crate::sync::yield_now();
             }
         }
         #[cfg(not(feature = "std"))]
+        if self.step <= SPIN_LIMIT as usize || !wait_hint() {
+            for _ in 0..spins {
+                spin_hint();
+            }
+        }
+
+        if self.step <= SPIN_LIMIT as usize {
+            self.step += 1;
+        }
+    }
+    /// Number of spins of the next `snooze_proportional(contention)`, if it spins.
+    #[inline]
+    fn spins(&self, contention: usize) -> usize {
+        let base = if SPIN_LIMIT < 0 {
+            1usize << (-SPIN_LIMIT - 1)
+        } else {
+            1 << self.step
+        };
+        base.saturating_mul(contention.max(1))
+    }
+    /// Starts over from the shortest spin, e.g. when moving on to the next independent wait point.
+    #[inline]
+    pub fn reset(&mut self) {
+        self.step = 1;
+    }
+    /// Returns `true` once `snooze` is yielding the thread instead of spinning (past the spinning phase
+    /// without `std`), e.g. to switch to blocking the thread.
+    ///
+    /// Always `true` for `SPIN_LIMIT` 0, and always `false` for negative `SPIN_LIMIT`.
+    #[inline]
+    pub fn is_completed(&self) -> bool {
+        SPIN_LIMIT >= 0 && self.step > SPIN_LIMIT as usize
+    }
+}
+
+/// `Backoff` that spins a random number of times in `1..=(1 << step)` instead of exactly `1 << step`, so
+/// threads that collided once don't keep colliding in lockstep.
+///
+/// Uses a xorshift generator, seeded differently for every instance.
+pub struct JitteredBackoff<const SPIN_LIMIT: isize> {
+    step: usize,
+    rng: u32,
+}
+static JITTER_SEED: core::sync::atomic::AtomicU32 = core::sync::atomic::AtomicU32::new(0);
+impl<const SPIN_LIMIT: isize> Default for JitteredBackoff<SPIN_LIMIT> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl<const SPIN_LIMIT: isize> JitteredBackoff<SPIN_LIMIT> {
+    #[inline]
+    pub fn new() -> Self {
+        // Weyl sequence, so consecutive instances start far apart. Xorshift state must not be 0.
+        let seed = JITTER_SEED.fetch_add(0x9E37_79B9, core::sync::atomic::Ordering::Relaxed);
+        Self {
+            step: 1,
+            rng: seed.wrapping_mul(0x85EB_CA6B) | 1,
+        }
+    }
+    #[inline]
+    fn spins(&mut self, shift: usize) -> usize {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 17;
+        self.rng ^= self.rng << 5;
+        (self.rng as usize & ((1usize << shift) - 1)) + 1
+    }
+    #[inline]
+    pub fn snooze(&mut self) {
+        if SPIN_LIMIT < 0 {
+            for _ in 0..self.spins((-SPIN_LIMIT - 1) as usize) {
+                spin_hint();
+            }
+            return;
+        }
+
+        #[cfg(feature = "std")]
         {
-            for _ in 0..1 << self.step {
-                core::hint::spin_loop();
+            if self.step <= SPIN_LIMIT as usize {
+                for _ in 0..self.spins(self.step) {
+                    spin_hint();
+                }
+            } else {
+                crate::sync::yield_now();
+            }
+        }
+        #[cfg(not(feature = "std"))]
+        if self.step <= SPIN_LIMIT as usize || !wait_hint() {
+            for _ in 0..self.spins(self.step) {
+                spin_hint();
             }
         }
 
@@ -43,11 +203,130 @@ impl<const SPIN_LIMIT: isize> Backoff<SPIN_LIMIT> {
             self.step += 1;
         }
     }
+    /// Starts over from the shortest spin.
+    #[inline]
+    pub fn reset(&mut self) {
+        self.step = 1;
+    }
+    /// Same as `Backoff::is_completed`.
+    #[inline]
+    pub fn is_completed(&self) -> bool {
+        SPIN_LIMIT >= 0 && self.step > SPIN_LIMIT as usize
+    }
+}
+
+/// Runtime equivalent of the `Backoff` generic param.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BackoffPolicy {
+    /// Same as `Backoff<0>`.
+    AlwaysYield,
+    /// Same as `Backoff<limit>`.
+    SpinThenYield { limit: u32 },
+    /// Executes `1 << shift` spins on every snooze, without ever yielding. Same as `Backoff<{ -(shift + 1) }>`.
+    ///
+    /// Shifts (here, and the steps of `SpinThenYield`) are clamped to 63.
+    SpinOnly { shift: u32 },
+}
+impl Default for BackoffPolicy {
+    #[inline]
+    fn default() -> Self {
+        Self::SpinThenYield {
+            limit: DEFAULT_SPIN_LIMIT as u32,
+        }
+    }
+}
+
+/// `Backoff` with the policy chosen at runtime (e.g. from a config file), at the cost of a branch per snooze.
+///
+/// Use `Backoff` on the hot paths.
+#[derive(Clone, Debug)]
+pub struct DynBackoff {
+    policy: BackoffPolicy,
+    step: u32,
+}
+impl Default for DynBackoff {
+    #[inline]
+    fn default() -> Self {
+        Self::new(BackoffPolicy::default())
+    }
+}
+/// `1 << shift` for a runtime shift, clamped so it can't overflow.
+#[inline(always)]
+fn dyn_spins(shift: u32) -> u64 {
+    1 << shift.min(u64::BITS - 1)
+}
+impl DynBackoff {
+    #[inline]
+    pub const fn new(policy: BackoffPolicy) -> Self {
+        Self { policy, step: 1 }
+    }
+    #[inline]
+    pub fn policy(&self) -> BackoffPolicy {
+        self.policy
+    }
+    #[inline]
+    pub fn snooze(&mut self) {
+        let limit = match self.policy {
+            BackoffPolicy::SpinOnly { shift } => {
+                for _ in 0..dyn_spins(shift) {
+                    spin_hint();
+                }
+                return;
+            }
+            BackoffPolicy::AlwaysYield => 0,
+            BackoffPolicy::SpinThenYield { limit } => limit,
+        };
+
+        #[cfg(feature = "std")]
+        {
+            if self.step <= limit {
+                for _ in 0..dyn_spins(self.step) {
+                    spin_hint();
+                }
+            } else {
+                crate::sync::yield_now();
+            }
+        }
+        #[cfg(not(feature = "std"))]
+        if self.step <= limit || !wait_hint() {
+            for _ in 0..dyn_spins(self.step) {
+                spin_hint();
+            }
+        }
+
+        if self.step <= limit {
+            self.step = self.step.saturating_add(1);
+        }
+    }
+    /// Starts over from the shortest spin.
+    #[inline]
+    pub fn reset(&mut self) {
+        self.step = 1;
+    }
+    /// Returns `true` once `snooze` is yielding the thread instead of spinning.
+    ///
+    /// Always `false` for `SpinOnly`.
+    #[inline]
+    pub fn is_completed(&self) -> bool {
+        match self.policy {
+            BackoffPolicy::AlwaysYield => true,
+            BackoffPolicy::SpinThenYield { limit } => self.step > limit,
+            BackoffPolicy::SpinOnly { .. } => false,
+        }
+    }
 }
 
 pub struct LinearBackoff<const MAX_SPIN_STEPS: usize, const SPINS_PER_STEP: usize> {
     step: usize,
 }
+impl<const MAX_SPIN_STEPS: usize, const SPINS_PER_STEP: usize> Default
+    for LinearBackoff<MAX_SPIN_STEPS, SPINS_PER_STEP>
+{
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
 impl<const MAX_SPIN_STEPS: usize, const SPINS_PER_STEP: usize>
     LinearBackoff<MAX_SPIN_STEPS, SPINS_PER_STEP>
 {
@@ -59,20 +338,188 @@ impl<const MAX_SPIN_STEPS: usize, const SPINS_PER_STEP: usize>
     pub fn snooze(&mut self) {
         if self.step < MAX_SPIN_STEPS {
             for _ in 0..SPINS_PER_STEP {
-                core::hint::spin_loop();
+                spin_hint();
             }
             self.step += 1;
             return;
         }
         #[cfg(feature = "std")]
         {
-            std::thread::yield_now();
+            crate::sync::yield_now();
         }
         #[cfg(not(feature = "std"))]
-        {
+        if !wait_hint() {
             for _ in 0..SPINS_PER_STEP {
-                core::hint::spin_loop();
+                spin_hint();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reset_starts_over() {
+        let mut backoff = Backoff::<3>::new();
+        for _ in 0..10 {
+            backoff.snooze();
+        }
+        assert_eq!(backoff.step, 4);
+        backoff.reset();
+        assert_eq!(backoff.step, 1);
+        backoff.snooze();
+        assert_eq!(backoff.step, 2);
+    }
+
+    #[test]
+    fn is_completed_positive_limit() {
+        let mut backoff = Backoff::<2>::new();
+        assert!(!backoff.is_completed());
+        backoff.snooze();
+        assert!(!backoff.is_completed());
+        backoff.snooze();
+        assert!(backoff.is_completed());
+        backoff.reset();
+        assert!(!backoff.is_completed());
+    }
+
+    #[test]
+    fn is_completed_zero_limit() {
+        let backoff = Backoff::<0>::new();
+        assert!(backoff.is_completed());
+    }
+
+    #[test]
+    fn is_completed_negative_limit() {
+        let mut backoff = Backoff::<-1>::new();
+        for _ in 0..10 {
+            backoff.snooze();
+            assert!(!backoff.is_completed());
+        }
+    }
+
+    #[cfg(feature = "spin-hook")]
+    #[test]
+    fn spin_hook_is_invoked() {
+        use core::sync::atomic::{AtomicUsize, Ordering};
+        static CALLS: AtomicUsize = AtomicUsize::new(0);
+        fn hook() {
+            CALLS.fetch_add(1, Ordering::Relaxed);
+        }
+        set_spin_hook(hook);
+        let mut backoff = Backoff::<-1>::new();
+        backoff.snooze();
+        assert!(CALLS.load(Ordering::Relaxed) >= 1);
+    }
+
+    #[test]
+    fn dyn_always_yield() {
+        let mut backoff = DynBackoff::new(BackoffPolicy::AlwaysYield);
+        assert!(backoff.is_completed());
+        backoff.snooze();
+        assert!(backoff.is_completed());
+        assert_eq!(backoff.policy(), BackoffPolicy::AlwaysYield);
+    }
+
+    #[test]
+    fn dyn_spin_then_yield() {
+        let mut backoff = DynBackoff::new(BackoffPolicy::SpinThenYield { limit: 2 });
+        assert!(!backoff.is_completed());
+        backoff.snooze();
+        assert!(!backoff.is_completed());
+        backoff.snooze();
+        assert!(backoff.is_completed());
+        backoff.snooze();
+        assert!(backoff.is_completed());
+        backoff.reset();
+        assert!(!backoff.is_completed());
+        assert_eq!(
+            DynBackoff::default().policy(),
+            BackoffPolicy::SpinThenYield {
+                limit: DEFAULT_SPIN_LIMIT as u32
             }
+        );
+    }
+
+    #[test]
+    fn dyn_spin_only() {
+        let mut backoff = DynBackoff::new(BackoffPolicy::SpinOnly { shift: 3 });
+        for _ in 0..10 {
+            backoff.snooze();
+            assert!(!backoff.is_completed());
+        }
+    }
+
+    #[test]
+    fn dyn_spins_clamped() {
+        assert_eq!(dyn_spins(3), 8);
+        assert_eq!(dyn_spins(63), 1 << 63);
+        assert_eq!(dyn_spins(64), 1 << 63);
+        assert_eq!(dyn_spins(u32::MAX), 1 << 63);
+    }
+
+    #[test]
+    fn spins_scale_with_contention() {
+        let mut backoff = Backoff::<3>::new();
+        assert_eq!(backoff.spins(0), backoff.spins(1));
+        assert_eq!(backoff.spins(4), 4 * backoff.spins(1));
+        backoff.snooze_proportional(4);
+        assert_eq!(backoff.step, 2);
+        assert_eq!(backoff.spins(8), 8 * backoff.spins(1));
+        assert_eq!(backoff.spins(usize::MAX), usize::MAX);
+
+        let spin_only = Backoff::<-3>::new();
+        assert_eq!(spin_only.spins(3), 3 * spin_only.spins(1));
+    }
+
+    #[test]
+    fn jittered_spins_vary_within_range() {
+        let mut backoff = JitteredBackoff::<6>::new();
+        let counts: Vec<usize> = (0..1000).map(|_| backoff.spins(6)).collect();
+        assert!(counts.iter().all(|&n| (1..=64).contains(&n)));
+        let min = *counts.iter().min().unwrap();
+        let max = *counts.iter().max().unwrap();
+        assert!(max - min > 32, "spins barely vary: {min}..={max}");
+        // Shift of 0 always spins once.
+        assert!((0..100).all(|_| backoff.spins(0) == 1));
+
+        let mut other = JitteredBackoff::<6>::new();
+        let other_counts: Vec<usize> = (0..1000).map(|_| other.spins(6)).collect();
+        assert_ne!(counts, other_counts);
+    }
+
+    #[cfg(all(feature = "wait-hook", not(feature = "std")))]
+    #[test]
+    fn wait_hook_past_spin_limit() {
+        use core::cell::Cell;
+        std::thread_local! {
+            static CALLS: Cell<usize> = const { Cell::new(0) };
         }
+        fn hook() {
+            CALLS.with(|calls| calls.set(calls.get() + 1));
+        }
+        let calls = || CALLS.with(Cell::get);
+
+        // Without a hook, it keeps spinning.
+        let mut backoff = Backoff::<2>::new();
+        for _ in 0..4 {
+            backoff.snooze();
+        }
+        assert_eq!(calls(), 0);
+
+        set_wait_hook(hook);
+        let mut backoff = Backoff::<2>::new();
+        backoff.snooze();
+        backoff.snooze();
+        assert_eq!(calls(), 0);
+        backoff.snooze();
+        backoff.snooze();
+        assert_eq!(calls(), 2);
+        // Spin-only backoff never calls it.
+        let mut spin_only = Backoff::<-2>::new();
+        spin_only.snooze();
+        assert_eq!(calls(), 2);
     }
 }
@@ -0,0 +1,80 @@
+use alloc::sync::Arc;
+
+use crate::{backoff::DEFAULT_SPIN_LIMIT, spin_rwlock::SpinRwLockEx};
+
+pub type ConfigCell<T> = ConfigCellEx<DEFAULT_SPIN_LIMIT, T>;
+
+/// Hot-reloadable value. `reload` swaps in a new `Arc` under the write lock, and `get` returns a clone of the
+/// current one under the read lock, so concurrent `get`s don't serialize each other.
+///
+/// Snapshots returned by `get` stay valid (and unchanged) after a `reload`, the old value is dropped once the
+/// last snapshot of it is dropped.
+pub struct ConfigCellEx<const B: isize, T> {
+    lock: SpinRwLockEx<B, Arc<T>>,
+}
+impl<const B: isize, T> ConfigCellEx<B, T> {
+    #[inline]
+    pub fn new(val: T) -> Self {
+        Self {
+            lock: SpinRwLockEx::new(Arc::new(val)),
+        }
+    }
+    /// Returns a snapshot of the current value.
+    #[inline]
+    pub fn get(&self) -> Arc<T> {
+        Arc::clone(&self.lock.read())
+    }
+    /// Replaces the value, and returns the previous one.
+    #[inline]
+    pub fn reload(&self, val: T) -> Arc<T> {
+        self.lock.replace(Arc::new(val))
+    }
+    #[inline]
+    pub fn into_inner(self) -> Arc<T> {
+        self.lock.into_inner()
+    }
+}
+impl<const B: isize, T: Default> Default for ConfigCellEx<B, T> {
+    #[inline]
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+impl<const B: isize, T: core::fmt::Debug> core::fmt::Debug for ConfigCellEx<B, T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("ConfigCell")
+            .field("data", &self.get())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshot_survives_reload() {
+        let cell = ConfigCell::new(String::from("v1"));
+        let snapshot = cell.get();
+        let prev = cell.reload(String::from("v2"));
+        assert!(Arc::ptr_eq(&prev, &snapshot));
+        assert_eq!(*snapshot, "v1");
+        assert_eq!(*cell.get(), "v2");
+        drop(prev);
+        assert_eq!(Arc::strong_count(&snapshot), 1);
+        assert_eq!(*snapshot, "v1");
+    }
+
+    #[test]
+    fn concurrent_gets_share_the_read_lock() {
+        let cell = ConfigCell::new(1u32);
+        let guard = cell.lock.read();
+        // A `get` can run while another reader holds the lock.
+        std::thread::scope(|s| {
+            s.spawn(|| assert_eq!(*cell.get(), 1));
+        });
+        drop(guard);
+        cell.reload(2);
+        assert_eq!(*cell.get(), 2);
+    }
+}
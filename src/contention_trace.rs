@@ -0,0 +1,142 @@
+//! Lock contention events, enabled with the `tracing` feature.
+//!
+//! Acquire loops emit a `trace` event when the lock is contended, and another one with the number of snoozes
+//! once it is finally acquired. Uncontended acquisitions emit nothing.
+//!
+//! Without the feature, `ContentionTrace` is a ZST and all the events compile out.
+
+pub(crate) struct ContentionTrace {
+    #[cfg(feature = "tracing")]
+    contended: bool,
+    #[cfg(feature = "tracing")]
+    snoozes: usize,
+}
+impl ContentionTrace {
+    #[inline]
+    pub(crate) const fn new() -> Self {
+        Self {
+            #[cfg(feature = "tracing")]
+            contended: false,
+            #[cfg(feature = "tracing")]
+            snoozes: 0,
+        }
+    }
+    /// Starts tracing an acquisition that already failed its first attempt. `L` is the lock type.
+    #[inline]
+    pub(crate) fn contended<L: ?Sized>() -> Self {
+        let mut trace = Self::new();
+        trace.mark_contended::<L>();
+        trace
+    }
+    /// Call before every snooze in the acquire loop of `L`.
+    #[inline]
+    pub(crate) fn snoozed<L: ?Sized>(&mut self) {
+        #[cfg(feature = "tracing")]
+        {
+            self.mark_contended::<L>();
+            self.snoozes += 1;
+        }
+    }
+    #[inline]
+    pub(crate) fn acquired<L: ?Sized>(self) {
+        #[cfg(feature = "tracing")]
+        if self.contended {
+            tracing::trace!(
+                lock = core::any::type_name::<L>(),
+                snoozes = self.snoozes,
+                "contended lock acquired"
+            );
+        }
+    }
+    #[inline]
+    fn mark_contended<L: ?Sized>(&mut self) {
+        #[cfg(feature = "tracing")]
+        if !self.contended {
+            self.contended = true;
+            tracing::trace!(lock = core::any::type_name::<L>(), "lock contended");
+        }
+    }
+}
+
+#[cfg(all(test, feature = "tracing"))]
+mod tests {
+    use std::{
+        fmt::Write,
+        string::String,
+        sync::{Arc, Mutex},
+        vec::Vec,
+    };
+
+    use tracing::{
+        Event, Metadata, Subscriber,
+        field::{Field, Visit},
+        span::{Attributes, Id, Record},
+    };
+
+    use super::*;
+    use crate::spin_mutex::SpinMutex;
+
+    /// Collects every event as a `field=value` string.
+    #[derive(Clone, Default)]
+    struct Capture(Arc<Mutex<Vec<String>>>);
+    struct Fields(String);
+    impl Visit for Fields {
+        fn record_debug(&mut self, field: &Field, value: &dyn core::fmt::Debug) {
+            write!(self.0, "{}={:?} ", field.name(), value).unwrap();
+        }
+    }
+    impl Subscriber for Capture {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+        fn new_span(&self, _: &Attributes<'_>) -> Id {
+            Id::from_u64(1)
+        }
+        fn record(&self, _: &Id, _: &Record<'_>) {}
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+        fn event(&self, event: &Event<'_>) {
+            let mut fields = Fields(String::new());
+            event.record(&mut fields);
+            self.0.lock().unwrap().push(fields.0);
+        }
+        fn enter(&self, _: &Id) {}
+        fn exit(&self, _: &Id) {}
+    }
+
+    #[test]
+    fn contended_and_acquired_events() {
+        let capture = Capture::default();
+        tracing::subscriber::with_default(capture.clone(), || {
+            ContentionTrace::new().acquired::<u32>();
+            let mut trace = ContentionTrace::contended::<u32>();
+            trace.snoozed::<u32>();
+            trace.snoozed::<u32>();
+            trace.acquired::<u32>();
+        });
+        let events = capture.0.lock().unwrap();
+        assert_eq!(events.len(), 2);
+        assert!(events[0].contains("lock contended") && events[0].contains("lock=\"u32\""));
+        assert!(events[1].contains("contended lock acquired") && events[1].contains("snoozes=2"));
+    }
+
+    #[test]
+    fn spin_mutex_contention() {
+        let capture = Capture::default();
+        let mutex = SpinMutex::new(0);
+        std::thread::scope(|s| {
+            let guard = mutex.lock();
+            let waiter = s.spawn(|| {
+                tracing::subscriber::with_default(capture.clone(), || *mutex.lock() += 1);
+            });
+            while capture.0.lock().unwrap().is_empty() {
+                std::thread::yield_now();
+            }
+            drop(guard);
+            waiter.join().unwrap();
+        });
+        let events = capture.0.lock().unwrap();
+        assert_eq!(events.len(), 2);
+        assert!(events[0].contains("lock contended") && events[0].contains("SpinMutex"));
+        assert!(events[1].contains("contended lock acquired"));
+    }
+}
@@ -0,0 +1,105 @@
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use crate::{backoff::DEFAULT_SPIN_LIMIT, spin_mutex::SpinMutexEx, spin_seqlock::SpinSeqLockEx};
+
+pub type DoubleBuffer<T> = DoubleBufferEx<DEFAULT_SPIN_LIMIT, T>;
+
+/// Double-buffered value, for infrequent writes and frequent reads.
+///
+/// Writers write into the inactive buffer and then flip the active index, so readers of the active buffer
+/// are not disturbed by the write. Writers are serialized with each other. A reader that loaded the index
+/// right before a flip can still race with the next write into its buffer, but the buffers are seqlocks, so
+/// it never observes a torn value.
+pub struct DoubleBufferEx<const B: isize, T: Copy> {
+    buffers: [SpinSeqLockEx<B, T>; 2],
+    active: AtomicBool,
+    writer: SpinMutexEx<B, ()>,
+}
+impl<const B: isize, T: Copy> DoubleBufferEx<B, T> {
+    #[inline]
+    pub fn new(val: T) -> Self {
+        Self {
+            buffers: [SpinSeqLockEx::new(val), SpinSeqLockEx::new(val)],
+            active: AtomicBool::new(false),
+            writer: SpinMutexEx::new(()),
+        }
+    }
+    #[inline]
+    fn active(&self, order: Ordering) -> usize {
+        usize::from(self.active.load(order))
+    }
+    /// Optimistically reads the active buffer.
+    #[inline]
+    pub fn load(&self) -> T {
+        self.buffers[self.active(Ordering::Acquire)].load()
+    }
+    #[inline]
+    pub fn store(&self, val: T) {
+        self.update(|_| val);
+    }
+    /// Writes `f(current)` into the inactive buffer and publishes it. Returns the previous value.
+    #[inline]
+    pub fn update(&self, f: impl FnOnce(T) -> T) -> T {
+        let _writer = self.writer.lock();
+        let active = self.active(Ordering::Relaxed);
+        let prev = self.buffers[active].load();
+        self.buffers[1 - active].store(f(prev));
+        self.active.store(active == 0, Ordering::Release);
+        prev
+    }
+    #[inline]
+    pub fn into_inner(self) -> T {
+        let active = self.active(Ordering::Relaxed);
+        let [a, b] = self.buffers;
+        if active == 0 { a } else { b }.into_inner()
+    }
+}
+impl<const B: isize, T: Copy + Default> Default for DoubleBufferEx<B, T> {
+    #[inline]
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+impl<const B: isize, T: Copy + core::fmt::Debug> core::fmt::Debug for DoubleBufferEx<B, T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("DoubleBuffer")
+            .field("data", &self.load())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn update_returns_previous() {
+        let buffer = DoubleBuffer::new(1u32);
+        assert_eq!(buffer.update(|v| v + 1), 1);
+        buffer.store(5);
+        assert_eq!(buffer.load(), 5);
+        assert_eq!(buffer.into_inner(), 5);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "optimistic reads are disabled under miri")]
+    fn readers_never_see_torn_values() {
+        let buffer = DoubleBuffer::new([0u64; 32]);
+        let done = AtomicBool::new(false);
+        std::thread::scope(|s| {
+            for _ in 0..2 {
+                s.spawn(|| {
+                    while !done.load(Ordering::Relaxed) {
+                        let v = buffer.load();
+                        assert!(v.iter().all(|&x| x == v[0]), "torn read: {v:?}");
+                    }
+                });
+            }
+            for i in 1..=2000 {
+                buffer.store([i; 32]);
+            }
+            done.store(true, Ordering::Relaxed);
+        });
+        assert_eq!(buffer.load(), [2000; 32]);
+    }
+}
@@ -0,0 +1,227 @@
+use core::{
+    cell::UnsafeCell,
+    ops::{Deref, DerefMut},
+    ptr,
+    sync::atomic::{AtomicBool, AtomicPtr, Ordering},
+};
+
+use crate::{
+    backoff::{Backoff, DEFAULT_SPIN_LIMIT},
+    contention_trace::ContentionTrace,
+    lock_hierarchy::LockLevel,
+    ordering_info::OrderingInfo,
+};
+
+pub type FairSpinMutex<T> = FairSpinMutexEx<DEFAULT_SPIN_LIMIT, T>;
+pub type FairSpinMutexGuard<'a, T> = FairSpinMutexGuardEx<'a, DEFAULT_SPIN_LIMIT, T>;
+
+/// Queue node for `FairSpinMutexEx`, provided by the thread that wants to lock.
+///
+/// The node is linked into the lock's waiter queue for as long as the guard lives, and the next locker writes
+/// to it, so it must stay alive until the guard is dropped, see [`FairSpinMutexEx::lock_with_node`].
+pub struct Waiter {
+    next: AtomicPtr<Waiter>,
+    locked: AtomicBool,
+}
+impl Waiter {
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            next: AtomicPtr::new(ptr::null_mut()),
+            locked: AtomicBool::new(false),
+        }
+    }
+}
+impl Default for Waiter {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Fair (FIFO) MCS queue lock that doesn't need allocation.
+///
+/// Each waiter spins on its own `Waiter` node instead of the shared lock word, so it scales better than
+/// `SpinMutex` under high contention.
+///
+/// `A` sets the alignment of the lock, see [`align`](crate::align).
+pub struct FairSpinMutexEx<const S: isize, T, A = ()> {
+    data: UnsafeCell<T>,
+    tail: AtomicPtr<Waiter>,
+    level: LockLevel,
+    _align: [A; 0],
+}
+pub struct FairSpinMutexGuardEx<'a, const S: isize, T, A = ()> {
+    lock: &'a FairSpinMutexEx<S, T, A>,
+    node: &'a Waiter,
+}
+impl<const S: isize, T, A> Drop for FairSpinMutexGuardEx<'_, S, T, A> {
+    #[inline]
+    fn drop(&mut self) {
+        self.lock.level.released();
+        let node = ptr::from_ref(self.node).cast_mut();
+        let mut next = self.node.next.load(Ordering::Acquire);
+        if next.is_null() {
+            if self
+                .lock
+                .tail
+                .compare_exchange(node, ptr::null_mut(), Ordering::Release, Ordering::Relaxed)
+                .is_ok()
+            {
+                return;
+            }
+            // Someone swapped the tail, but did not link itself to our node yet.
+            let mut backoff = Backoff::<S>::new();
+            loop {
+                next = self.node.next.load(Ordering::Acquire);
+                if !next.is_null() {
+                    break;
+                }
+                backoff.snooze();
+            }
+        }
+        // Safety: successor keeps its node alive until it observes `locked == false`
+        unsafe { (*next).locked.store(false, Ordering::Release) };
+    }
+}
+impl<const S: isize, T, A> Deref for FairSpinMutexGuardEx<'_, S, T, A> {
+    type Target = T;
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        // Safety: safe to deref while we hold the lock
+        unsafe { &*self.lock.data.get() }
+    }
+}
+impl<const S: isize, T, A> DerefMut for FairSpinMutexGuardEx<'_, S, T, A> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // Safety: safe to deref while we hold the lock
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+impl<const S: isize, T: Default, A> Default for FairSpinMutexEx<S, T, A> {
+    #[inline]
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+impl<const S: isize, T, A> FairSpinMutexEx<S, T, A> {
+    #[inline]
+    pub const fn new(val: T) -> Self {
+        Self {
+            data: UnsafeCell::new(val),
+            tail: AtomicPtr::new(ptr::null_mut()),
+            level: LockLevel::NONE,
+            _align: [],
+        }
+    }
+    /// Assigns a level to this lock for the `lock-hierarchy` validation. No-op without the feature.
+    #[inline]
+    pub const fn with_level(mut self, level: usize) -> Self {
+        self.level = LockLevel::new(level);
+        self
+    }
+    /// Memory orderings used by this lock, see [`OrderingInfo`].
+    #[inline]
+    pub const fn ordering_info() -> OrderingInfo {
+        OrderingInfo::FAIR
+    }
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+    /// Locks with a `Waiter` node on the stack, and runs `callback` under the lock. The lock is released when
+    /// it returns (or panics).
+    #[inline]
+    pub fn with_lock<R>(&self, callback: impl FnOnce(&mut T) -> R) -> R {
+        let mut node = Waiter::new();
+        // Safety: the guard is dropped before `node`, since it's declared after it
+        let mut guard = unsafe { self.lock_with_node(&mut node) };
+        callback(&mut guard)
+    }
+    /// Enqueues `node` and spins on it until all the previous waiters released the lock.
+    ///
+    /// # Safety
+    /// The guard must be dropped before `node` is. The borrow checker already prevents dropping the node
+    /// first, but leaking the guard (e.g. `mem::forget`) keeps the node linked into the queue, and the next
+    /// locker then writes to it after it's gone. `with_lock` is a safe alternative.
+    #[inline]
+    pub unsafe fn lock_with_node<'a>(
+        &'a self,
+        node: &'a mut Waiter,
+    ) -> FairSpinMutexGuardEx<'a, S, T, A> {
+        self.level.check();
+        *node.next.get_mut() = ptr::null_mut();
+        *node.locked.get_mut() = true;
+        let node: &'a Waiter = node;
+        let node_ptr = ptr::from_ref(node).cast_mut();
+
+        let prev = self.tail.swap(node_ptr, Ordering::AcqRel);
+        if !prev.is_null() {
+            // Safety: predecessor keeps its node alive until it hands the lock over to us
+            unsafe { (*prev).next.store(node_ptr, Ordering::Release) };
+            let mut backoff = Backoff::<S>::new();
+            let mut trace = ContentionTrace::contended::<Self>();
+            while node.locked.load(Ordering::Acquire) {
+                trace.snoozed::<Self>();
+                backoff.snooze();
+            }
+            trace.acquired::<Self>();
+        }
+        self.level.acquired();
+        FairSpinMutexGuardEx { lock: self, node }
+    }
+}
+unsafe impl<const S: isize, T: Send, A> Send for FairSpinMutexEx<S, T, A> {}
+unsafe impl<const S: isize, T: Send, A> Sync for FairSpinMutexEx<S, T, A> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const THREADS: usize = 4;
+    const ITERS: usize = 2000;
+
+    #[test]
+    fn stress() {
+        let lock = FairSpinMutex::new((0usize, 0usize));
+        std::thread::scope(|s| {
+            for i in 0..THREADS {
+                let lock = &lock;
+                s.spawn(move || {
+                    for _ in 0..ITERS {
+                        if i % 2 == 0 {
+                            lock.with_lock(|(a, b)| {
+                                *a += 1;
+                                *b += 1;
+                            });
+                        } else {
+                            let mut node = Waiter::new();
+                            // Safety: the guard is dropped before the node
+                            let mut guard = unsafe { lock.lock_with_node(&mut node) };
+                            assert_eq!(guard.0, guard.1);
+                            guard.0 += 1;
+                            guard.1 += 1;
+                        }
+                    }
+                });
+            }
+        });
+        assert_eq!(lock.into_inner(), (THREADS * ITERS, THREADS * ITERS));
+    }
+
+    #[test]
+    fn node_reuse() {
+        let lock = FairSpinMutex::new(0);
+        let mut node = Waiter::new();
+        for _ in 0..3 {
+            // Safety: the guard is dropped before the node
+            *unsafe { lock.lock_with_node(&mut node) } += 1;
+        }
+        assert_eq!(lock.with_lock(|v| *v), 3);
+    }
+}
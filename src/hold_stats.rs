@@ -0,0 +1,166 @@
+//! Write lock hold time accounting, enabled with the `stats` feature.
+//!
+//! Write guards start a `HoldTimer` on acquisition, and add the elapsed time to the lock's `HoldStats` when they
+//! are dropped. Summed time is exposed as `total_write_hold_nanos` on the locks.
+//!
+//! `HoldStats` also counts the write acquisitions that had to wait for the lock, exposed as
+//! `contended_write_acquisitions`, and keeps a moving average of acquisition snoozes, for
+//! `SpinMutexEx::lock_adaptive`.
+//!
+//! Without the feature, both types are ZSTs and the accounting compiles out.
+#[cfg(feature = "stats")]
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+#[cfg(feature = "stats")]
+use std::time::Instant;
+
+pub(crate) struct HoldStats {
+    #[cfg(feature = "stats")]
+    total_nanos: AtomicU64,
+    #[cfg(feature = "stats")]
+    contended: AtomicUsize,
+    /// Moving average of snoozes, multiplied by `SNOOZES_WEIGHT`
+    #[cfg(feature = "stats")]
+    avg_snoozes: AtomicUsize,
+}
+#[cfg(feature = "stats")]
+const SNOOZES_WEIGHT: usize = 8;
+pub(crate) struct HoldTimer {
+    #[cfg(feature = "stats")]
+    start: Instant,
+}
+impl HoldStats {
+    #[inline]
+    pub(crate) const fn new() -> Self {
+        Self {
+            #[cfg(feature = "stats")]
+            total_nanos: AtomicU64::new(0),
+            #[cfg(feature = "stats")]
+            contended: AtomicUsize::new(0),
+            #[cfg(feature = "stats")]
+            avg_snoozes: AtomicUsize::new(0),
+        }
+    }
+    #[inline]
+    pub(crate) fn start(&self) -> HoldTimer {
+        HoldTimer {
+            #[cfg(feature = "stats")]
+            start: Instant::now(),
+        }
+    }
+    #[inline]
+    pub(crate) fn stop(&self, timer: &HoldTimer) {
+        #[cfg(feature = "stats")]
+        {
+            let nanos = u64::try_from(timer.start.elapsed().as_nanos()).unwrap_or(u64::MAX);
+            self.total_nanos.fetch_add(nanos, Ordering::Relaxed);
+        }
+        #[cfg(not(feature = "stats"))]
+        let _ = timer;
+    }
+    #[cfg(feature = "stats")]
+    #[inline]
+    pub(crate) fn total_nanos(&self) -> u64 {
+        self.total_nanos.load(Ordering::Relaxed)
+    }
+    /// Call once per acquisition that had to snooze at least once.
+    #[inline]
+    pub(crate) fn record_contended(&self) {
+        #[cfg(feature = "stats")]
+        self.contended.fetch_add(1, Ordering::Relaxed);
+    }
+    #[cfg(feature = "stats")]
+    #[inline]
+    pub(crate) fn contended(&self) -> usize {
+        self.contended.load(Ordering::Relaxed)
+    }
+    /// Adds `snoozes` to the moving average. Concurrent updates may get lost, which is fine for a heuristic.
+    #[cfg(feature = "stats")]
+    #[inline]
+    pub(crate) fn record_snoozes(&self, snoozes: usize) {
+        let avg = self.avg_snoozes.load(Ordering::Relaxed);
+        self.avg_snoozes.store(
+            avg - avg / SNOOZES_WEIGHT + snoozes.min(usize::MAX / SNOOZES_WEIGHT),
+            Ordering::Relaxed,
+        );
+    }
+    #[cfg(feature = "stats")]
+    #[inline]
+    pub(crate) fn avg_snoozes(&self) -> usize {
+        self.avg_snoozes.load(Ordering::Relaxed) / SNOOZES_WEIGHT
+    }
+}
+
+#[cfg(all(test, feature = "stats"))]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{spin_mutex::SpinMutex, spin_rwlock::SpinRwLock, spin_seqlock::SpinSeqLock};
+
+    const HOLD: Duration = Duration::from_millis(20);
+
+    fn assert_held_for(total_nanos: u64) {
+        let held = Duration::from_nanos(total_nanos);
+        assert!(held >= HOLD, "{held:?}");
+        assert!(held < HOLD * 50, "{held:?}");
+    }
+
+    #[test]
+    fn write_hold_time_is_recorded() {
+        let mutex = SpinMutex::new(0u32);
+        assert_eq!(mutex.total_write_hold_nanos(), 0);
+        {
+            let _guard = mutex.lock();
+            std::thread::sleep(HOLD);
+        }
+        assert_held_for(mutex.total_write_hold_nanos());
+
+        let rwlock = SpinRwLock::new(0u32);
+        {
+            let _guard = rwlock.write();
+            std::thread::sleep(HOLD);
+        }
+        assert_held_for(rwlock.total_write_hold_nanos());
+
+        let seqlock = SpinSeqLock::new(0u32);
+        {
+            let _guard = seqlock.write();
+            std::thread::sleep(HOLD);
+        }
+        assert_held_for(seqlock.total_write_hold_nanos());
+    }
+
+    #[test]
+    fn contended_write_acquisitions_are_counted() {
+        let mutex = SpinMutex::new(0u32);
+        let rwlock = SpinRwLock::new(0u32);
+        let seqlock = SpinSeqLock::new(0u32);
+        drop((mutex.lock(), rwlock.write(), seqlock.write()));
+        assert_eq!(mutex.contended_write_acquisitions(), 0);
+        assert_eq!(rwlock.contended_write_acquisitions(), 0);
+        assert_eq!(seqlock.contended_write_acquisitions(), 0);
+
+        // One waiter per lock, so each of them finds its lock held.
+        std::thread::scope(|s| {
+            let guard = mutex.lock();
+            let waiter = s.spawn(|| *mutex.lock() += 1);
+            std::thread::sleep(HOLD);
+            drop(guard);
+            waiter.join().unwrap();
+
+            let guard = rwlock.write();
+            let waiter = s.spawn(|| *rwlock.write() += 1);
+            std::thread::sleep(HOLD);
+            drop(guard);
+            waiter.join().unwrap();
+
+            let guard = seqlock.write();
+            let waiter = s.spawn(|| *seqlock.write() += 1);
+            std::thread::sleep(HOLD);
+            drop(guard);
+            waiter.join().unwrap();
+        });
+        assert_eq!(mutex.contended_write_acquisitions(), 1);
+        assert_eq!(rwlock.contended_write_acquisitions(), 1);
+        assert_eq!(seqlock.contended_write_acquisitions(), 1);
+    }
+}
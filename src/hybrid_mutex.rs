@@ -0,0 +1,143 @@
+use core::ops::{Deref, DerefMut};
+#[cfg(feature = "std-fallback")]
+use std::sync::{Mutex, MutexGuard, PoisonError, TryLockError};
+
+#[cfg(not(feature = "std-fallback"))]
+use crate::spin_mutex::{SpinMutex as Mutex, SpinMutexGuard as MutexGuard};
+
+/// Mutex with the `SpinMutex` API, that is a `SpinMutex` by default, and a `std::sync::Mutex` with the
+/// `std-fallback` feature. Lets the same code use spinning or OS blocking, depending on the target.
+///
+/// `std::sync::Mutex` poisoning is ignored, same as `SpinMutex` has none.
+pub struct HybridMutex<T> {
+    inner: Mutex<T>,
+}
+pub struct HybridMutexGuard<'a, T> {
+    inner: MutexGuard<'a, T>,
+}
+impl<T> HybridMutex<T> {
+    #[inline]
+    pub fn new(val: T) -> Self {
+        Self {
+            inner: Mutex::new(val),
+        }
+    }
+    #[inline]
+    pub fn lock(&self) -> HybridMutexGuard<'_, T> {
+        #[cfg(feature = "std-fallback")]
+        let inner = self.inner.lock().unwrap_or_else(PoisonError::into_inner);
+        #[cfg(not(feature = "std-fallback"))]
+        let inner = self.inner.lock();
+        HybridMutexGuard { inner }
+    }
+    #[inline]
+    pub fn try_lock(&self) -> Option<HybridMutexGuard<'_, T>> {
+        #[cfg(feature = "std-fallback")]
+        let inner = match self.inner.try_lock() {
+            Ok(inner) => inner,
+            Err(TryLockError::Poisoned(poisoned)) => poisoned.into_inner(),
+            Err(TryLockError::WouldBlock) => return None,
+        };
+        #[cfg(not(feature = "std-fallback"))]
+        let inner = self.inner.try_lock()?;
+        Some(HybridMutexGuard { inner })
+    }
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        #[cfg(feature = "std-fallback")]
+        return self.inner.get_mut().unwrap_or_else(PoisonError::into_inner);
+        #[cfg(not(feature = "std-fallback"))]
+        return self.inner.get_mut();
+    }
+    #[inline]
+    pub fn into_inner(self) -> T {
+        #[cfg(feature = "std-fallback")]
+        return self
+            .inner
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner);
+        #[cfg(not(feature = "std-fallback"))]
+        return self.inner.into_inner();
+    }
+}
+impl<T> Deref for HybridMutexGuard<'_, T> {
+    type Target = T;
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+impl<T> DerefMut for HybridMutexGuard<'_, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.inner
+    }
+}
+impl<T: Default> Default for HybridMutex<T> {
+    #[inline]
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+impl<T: core::fmt::Debug> core::fmt::Debug for HybridMutex<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        self.inner.fmt(f)
+    }
+}
+
+// Same tests run against both backends: `cargo test` and `cargo test --features std-fallback`.
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lock_and_try_lock() {
+        let mutex = HybridMutex::new(1);
+        {
+            let mut guard = mutex.lock();
+            *guard += 1;
+            assert!(mutex.try_lock().is_none());
+        }
+        let mut guard = mutex.try_lock().unwrap();
+        assert_eq!(*guard, 2);
+        *guard = 3;
+        drop(guard);
+        assert_eq!(*mutex.lock(), 3);
+    }
+
+    #[test]
+    fn get_mut_and_into_inner() {
+        let mut mutex = HybridMutex::<u32>::default();
+        *mutex.get_mut() = 5;
+        assert_eq!(mutex.into_inner(), 5);
+    }
+
+    #[test]
+    fn concurrent_increments() {
+        let mutex = HybridMutex::new(0usize);
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    for _ in 0..1000 {
+                        *mutex.lock() += 1;
+                    }
+                });
+            }
+        });
+        assert_eq!(mutex.into_inner(), 4000);
+    }
+
+    #[test]
+    fn usable_after_panic_while_locked() {
+        let mutex = HybridMutex::new(0);
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let mut guard = mutex.lock();
+            *guard = 1;
+            panic!("while locked");
+        }));
+        assert!(result.is_err());
+        assert_eq!(*mutex.try_lock().unwrap(), 1);
+        *mutex.lock() = 2;
+        assert_eq!(mutex.into_inner(), 2);
+    }
+}
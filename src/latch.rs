@@ -0,0 +1,105 @@
+use core::sync::atomic::Ordering;
+
+use crate::{
+    atomic_t_mu::AtomicT8,
+    backoff::{Backoff, DEFAULT_SPIN_LIMIT},
+};
+
+pub type Latch<S> = LatchEx<DEFAULT_SPIN_LIMIT, S>;
+
+/// Monotonic state machine stored in a single byte.
+///
+/// `S` is a byte-sized state type (usually a fieldless `#[repr(u8)]` enum) whose `Ord` defines the order of
+/// the states. The state can only ever move forward.
+pub struct LatchEx<const B: isize, S: Copy + Ord> {
+    state: AtomicT8<S>,
+}
+impl<const B: isize, S: Copy + Ord> LatchEx<B, S> {
+    #[inline]
+    pub const fn new(initial: S) -> Self {
+        Self {
+            state: AtomicT8::new(initial),
+        }
+    }
+    #[inline]
+    pub fn load(&self) -> S {
+        self.state.load(Ordering::Acquire)
+    }
+    #[inline]
+    pub fn into_inner(self) -> S {
+        self.state.into_inner()
+    }
+    /// Moves the latch to `state`.
+    ///
+    /// Fails with the current state if it is not strictly before `state`.
+    #[inline]
+    pub fn advance_to(&self, state: S) -> Result<(), S> {
+        self.state
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |current| {
+                (current < state).then_some(state)
+            })
+            .map(|_| ())
+    }
+    /// Spins until the latch reaches `state` (or any state after it).
+    #[inline]
+    pub fn wait_until(&self, state: S) -> S {
+        let mut backoff = Backoff::<B>::new();
+        loop {
+            let current = self.load();
+            if current >= state {
+                return current;
+            }
+            backoff.snooze();
+        }
+    }
+}
+impl<const B: isize, S: Copy + Ord + core::fmt::Debug> core::fmt::Debug for LatchEx<B, S> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Latch")
+            .field("state", &self.load())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+    #[repr(u8)]
+    enum Task {
+        Pending,
+        Running,
+        Done,
+    }
+
+    #[test]
+    fn legal_transitions() {
+        let latch = Latch::new(Task::Pending);
+        assert_eq!(latch.advance_to(Task::Running), Ok(()));
+        assert_eq!(latch.advance_to(Task::Done), Ok(()));
+        assert_eq!(latch.into_inner(), Task::Done);
+
+        let latch = Latch::new(Task::Pending);
+        assert_eq!(latch.advance_to(Task::Done), Ok(()));
+        assert_eq!(latch.load(), Task::Done);
+    }
+
+    #[test]
+    fn illegal_transitions() {
+        let latch = Latch::new(Task::Running);
+        assert_eq!(latch.advance_to(Task::Pending), Err(Task::Running));
+        assert_eq!(latch.advance_to(Task::Running), Err(Task::Running));
+        assert_eq!(latch.load(), Task::Running);
+    }
+
+    #[test]
+    fn wait_until_other_thread_advances() {
+        let latch = Latch::new(Task::Pending);
+        std::thread::scope(|s| {
+            let waiter = s.spawn(|| latch.wait_until(Task::Running));
+            latch.advance_to(Task::Done).unwrap();
+            assert_eq!(waiter.join().unwrap(), Task::Done);
+        });
+    }
+}
@@ -1,12 +1,56 @@
 //! Basic utils for concurrent programming. Backoff, spinlocks, seqlock, atomic type wrappers.
-#![cfg_attr(not(feature = "std"), no_std)]
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+// All the locks are built on native compare-and-swap of `core` atomics, so fail with one clear error, instead of
+// dozens of missing method errors.
+#[cfg(not(target_has_atomic = "ptr"))]
+compile_error!(
+    "`atomics` needs native compare-and-swap atomics, which this target doesn't have. The `portable-atomic` \
+     feature only helps on targets that lack wider (64/128-bit) atomics, for `atomic_t::AtomicT64`/`AtomicT128`."
+);
+
+pub mod atomic_storage;
+pub mod atomic_option;
 pub mod atomic_t;
 pub mod atomic_t_mu;
+pub mod align;
+pub mod atomic_value;
 pub mod backoff;
+#[cfg(feature = "alloc")]
+pub mod config_cell;
+pub mod double_buffer;
+pub mod fair_spin_mutex;
+pub mod latch;
+mod contention_trace;
+mod hold_stats;
+pub mod hybrid_mutex;
+mod lock_hierarchy;
+pub mod ordered_atomic_t;
+pub mod ordering_info;
+#[cfg(feature = "lock-api")]
+pub mod raw_spin_mutex;
+#[cfg(feature = "lock-api")]
+pub mod raw_spin_rwlock;
+pub mod seq_cell;
+pub mod seqlock_cache;
 pub mod spin_mutex;
 pub mod spin_rwlock;
 pub mod spin_seqlock;
+mod sync;
+pub mod timestamped;
 pub mod atomic_spin_seqlock;
+#[cfg(feature = "std")]
+pub mod poisoning_spin_mutex;
+#[cfg(feature = "std")]
+pub mod sharded_counter;
+#[cfg(feature = "std")]
+pub mod tracked_spin_mutex;
+#[cfg(feature = "std")]
+pub mod watched_seqlock;
+#[cfg(feature = "testing")]
+pub mod testing;
 
 macro_rules! const_type_assert {
     ($t:ident, $c:expr, $($arg:tt)*) => {{
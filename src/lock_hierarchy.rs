@@ -0,0 +1,138 @@
+//! Lock ordering validation, enabled with the `lock-hierarchy` feature.
+//!
+//! Each lock can be assigned a level with `with_level`. Every thread keeps a stack of the levels of the locks
+//! it holds, and acquiring a lock whose level is not strictly greater than the last acquired one panics.
+//! Locks with level 0 (default) are not validated.
+//!
+//! Without the feature, `LockLevel` is a ZST and all the checks compile out.
+#[cfg(feature = "lock-hierarchy")]
+use std::{cell::RefCell, vec::Vec};
+
+#[cfg(feature = "lock-hierarchy")]
+std::thread_local! {
+    static HELD_LEVELS: RefCell<Vec<usize>> = const { RefCell::new(Vec::new()) };
+}
+
+#[derive(Clone, Copy)]
+pub(crate) struct LockLevel {
+    #[cfg(feature = "lock-hierarchy")]
+    level: usize,
+}
+impl LockLevel {
+    pub(crate) const NONE: Self = Self::new(0);
+
+    #[inline]
+    pub(crate) const fn new(level: usize) -> Self {
+        #[cfg(not(feature = "lock-hierarchy"))]
+        let _ = level;
+        Self {
+            #[cfg(feature = "lock-hierarchy")]
+            level,
+        }
+    }
+    /// Panics if acquiring a lock with this level would violate the hierarchy.
+    #[inline]
+    pub(crate) fn check(self) {
+        #[cfg(feature = "lock-hierarchy")]
+        if self.level != 0 {
+            HELD_LEVELS.with_borrow(|held| {
+                if let Some(&top) = held.last() {
+                    assert!(
+                        self.level > top,
+                        "Lock hierarchy violation: acquiring lock with level {} while holding level {top}",
+                        self.level
+                    );
+                }
+            });
+        }
+    }
+    /// Records that the lock was acquired. `check` must have been called before acquiring it, so a violation
+    /// panics while the lock is still unlocked.
+    #[inline]
+    pub(crate) fn acquired(self) {
+        #[cfg(feature = "lock-hierarchy")]
+        if self.level != 0 {
+            HELD_LEVELS.with_borrow_mut(|held| held.push(self.level));
+        }
+    }
+    #[inline]
+    pub(crate) fn released(self) {
+        #[cfg(feature = "lock-hierarchy")]
+        if self.level != 0 {
+            HELD_LEVELS.with_borrow_mut(|held| {
+                // guards don't have to be dropped in reverse order
+                if let Some(i) = held.iter().rposition(|&l| l == self.level) {
+                    held.remove(i);
+                }
+            });
+        }
+    }
+}
+
+#[cfg(all(test, feature = "lock-hierarchy"))]
+mod tests {
+    use std::panic::{AssertUnwindSafe, catch_unwind};
+
+    use crate::{
+        atomic_spin_seqlock::SpinSeqLockAtomicPtr, spin_mutex::SpinMutex, spin_rwlock::SpinRwLock,
+        spin_seqlock::SpinSeqLock,
+    };
+
+    #[test]
+    fn in_order() {
+        let a = SpinMutex::new(0).with_level(1);
+        let b = SpinRwLock::new(0).with_level(2);
+        let c = SpinSeqLock::new(0).with_level(3);
+        let _a = a.lock();
+        let _b = b.read();
+        let _c = c.write();
+    }
+
+    #[test]
+    #[should_panic(expected = "Lock hierarchy violation")]
+    fn out_of_order() {
+        let a = SpinMutex::new(0).with_level(1);
+        let b = SpinMutex::new(0).with_level(2);
+        let _b = b.lock();
+        let _a = a.lock();
+    }
+
+    #[test]
+    fn try_paths_panic_before_locking() {
+        let low = SpinMutex::new(0).with_level(1);
+        let mutex = SpinMutex::new(0).with_level(1);
+        let rwlock = SpinRwLock::new(0).with_level(1);
+        let seqlock = SpinSeqLock::new(0).with_level(1);
+        let ptr_seqlock = SpinSeqLockAtomicPtr::<u32>::new(core::ptr::null_mut()).with_level(1);
+        let (version, _) = ptr_seqlock.load_versioned();
+        {
+            let _low = low.lock();
+            assert!(catch_unwind(AssertUnwindSafe(|| mutex.try_lock().is_some())).is_err());
+            assert!(catch_unwind(AssertUnwindSafe(|| rwlock.try_read().is_some())).is_err());
+            assert!(catch_unwind(AssertUnwindSafe(|| rwlock.try_write().is_some())).is_err());
+            assert!(catch_unwind(AssertUnwindSafe(|| seqlock.try_read().is_some())).is_err());
+            assert!(catch_unwind(AssertUnwindSafe(|| seqlock.try_write().is_some())).is_err());
+            let cas = || {
+                ptr_seqlock.compare_exchange_versioned(
+                    core::ptr::null_mut(),
+                    version,
+                    core::ptr::null_mut(),
+                )
+            };
+            assert!(catch_unwind(AssertUnwindSafe(cas)).is_err());
+        }
+        assert!(!mutex.is_locked());
+        assert!(!rwlock.is_locked());
+        assert!(mutex.try_lock().is_some());
+        assert!(rwlock.try_write().is_some());
+        assert!(seqlock.try_write().is_some());
+        assert_eq!(
+            ptr_seqlock.compare_exchange_versioned(
+                core::ptr::null_mut(),
+                version,
+                core::ptr::null_mut()
+            ),
+            Ok(())
+        );
+    }
+}
@@ -0,0 +1,151 @@
+//! `atomic_t_mu::AtomicT*` wrappers with the memory ordering baked into the type.
+//!
+//! `load`/`store`/RMW methods don't take an `Ordering`, they always use the one from the `OrderingMarker`.
+//! Marker trait is sealed, and there is no marker for `Relaxed`, so it can't be used by accident.
+//!
+//! ```
+//! use atomics::ordered_atomic_t::{AcqRelOrd, OrderedAtomicT32};
+//!
+//! let flag = OrderedAtomicT32::<u32, AcqRelOrd>::new(0);
+//! flag.store(1);
+//! assert_eq!(flag.load(), 1);
+//! ```
+//!
+//! A weaker ordering can't be passed:
+//! ```compile_fail
+//! use atomics::ordered_atomic_t::{AcqRelOrd, OrderedAtomicT32};
+//! use core::sync::atomic::Ordering;
+//!
+//! let flag = OrderedAtomicT32::<u32, AcqRelOrd>::new(0);
+//! flag.store(1, Ordering::Relaxed);
+//! ```
+//!
+//! And no `Relaxed` marker can be added:
+//! ```compile_fail
+//! use atomics::ordered_atomic_t::OrderingMarker;
+//! use core::sync::atomic::Ordering;
+//!
+//! struct RelaxedOrd;
+//! impl OrderingMarker for RelaxedOrd {
+//!     const LOAD: Ordering = Ordering::Relaxed;
+//!     const STORE: Ordering = Ordering::Relaxed;
+//!     const RMW: Ordering = Ordering::Relaxed;
+//! }
+//! ```
+use core::{fmt, marker::PhantomData, mem, sync::atomic::Ordering};
+
+use crate::atomic_t_mu;
+
+mod sealed {
+    pub trait Sealed {}
+}
+pub trait OrderingMarker: sealed::Sealed {
+    const LOAD: Ordering;
+    const STORE: Ordering;
+    const RMW: Ordering;
+}
+/// `Acquire` loads, `Release` stores, `AcqRel` read-modify-writes.
+pub struct AcqRelOrd;
+/// `SeqCst` everything.
+pub struct SeqCstOrd;
+impl sealed::Sealed for AcqRelOrd {}
+impl OrderingMarker for AcqRelOrd {
+    const LOAD: Ordering = Ordering::Acquire;
+    const STORE: Ordering = Ordering::Release;
+    const RMW: Ordering = Ordering::AcqRel;
+}
+impl sealed::Sealed for SeqCstOrd {}
+impl OrderingMarker for SeqCstOrd {
+    const LOAD: Ordering = Ordering::SeqCst;
+    const STORE: Ordering = Ordering::SeqCst;
+    const RMW: Ordering = Ordering::SeqCst;
+}
+
+macro_rules! impl_ordered_atomic_t {
+  ($($struct_name:ident, $inner:ident);*;) => {
+    $(
+    pub struct $struct_name<T: Copy, O: OrderingMarker> {
+      data: atomic_t_mu::$inner<T>,
+      _pd:  PhantomData<O>,
+    }
+    impl<T: Copy, O: OrderingMarker> $struct_name<T, O> {
+      #[inline]
+      pub const fn new(value: T) -> Self { Self { data: atomic_t_mu::$inner::new(value), _pd: PhantomData } }
+      #[inline]
+      pub fn get_mut(&mut self) -> &mut T { self.data.get_mut() }
+      #[inline]
+      pub fn into_inner(self) -> T { self.data.into_inner() }
+      #[inline]
+      pub fn load(&self) -> T { self.data.load(O::LOAD) }
+      #[inline]
+      pub fn store(&self, value: T) { self.data.store(value, O::STORE) }
+      #[inline]
+      pub fn swap(&self, value: T) -> T { self.data.swap(value, O::RMW) }
+      #[inline]
+      pub fn swap_mut(&mut self, value: T) -> T { mem::replace(self.get_mut(), value) }
+      #[inline]
+      pub fn compare_exchange(&self, current: T, new: T) -> Result<T, T> {
+        self.data.compare_exchange(current, new, O::RMW, O::LOAD)
+      }
+      #[inline]
+      pub fn compare_exchange_weak(&self, current: T, new: T) -> Result<T, T> {
+        self.data.compare_exchange_weak(current, new, O::RMW, O::LOAD)
+      }
+      #[inline]
+      pub fn fetch_update(&self, f: impl FnMut(T) -> Option<T>) -> Result<T, T> {
+        self.data.fetch_update(O::RMW, O::LOAD, f)
+      }
+    }
+    impl<T: Default + Copy, O: OrderingMarker> $struct_name<T, O> {
+      #[inline]
+      pub fn take(&self) -> T { self.swap(T::default()) }
+    }
+    impl<T: Default + Copy, O: OrderingMarker> Default for $struct_name<T, O> {
+      fn default() -> Self { Self::new(T::default()) }
+    }
+    impl<T: fmt::Debug + Copy, O: OrderingMarker> fmt::Debug for $struct_name<T, O> {
+      fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.load(), f)
+      }
+    }
+    impl<T: Copy, O: OrderingMarker> From<T> for $struct_name<T, O> {
+      #[inline]
+      fn from(value: T) -> Self { Self::new(value) }
+    }
+    )*
+  };
+}
+impl_ordered_atomic_t! {
+  OrderedAtomicT8, AtomicT8;
+  OrderedAtomicT16, AtomicT16;
+  OrderedAtomicT32, AtomicT32;
+  OrderedAtomicTUsize, AtomicTUsize;
+}
+#[cfg(target_has_atomic = "64")]
+impl_ordered_atomic_t! {
+  OrderedAtomicT64, AtomicT64;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn baked_in_orderings() {
+        let value = OrderedAtomicT32::<u32, SeqCstOrd>::new(1);
+        assert_eq!(value.swap(2), 1);
+        assert_eq!(value.compare_exchange(2, 3), Ok(2));
+        assert_eq!(value.compare_exchange(2, 4), Err(3));
+        assert_eq!(value.fetch_update(|v| Some(v * 2)), Ok(3));
+        assert_eq!(value.take(), 6);
+        assert_eq!(value.load(), 0);
+    }
+
+    #[test]
+    fn padded_type() {
+        let value = OrderedAtomicT32::<(u8, u16), AcqRelOrd>::new((1, 2));
+        value.store((3, 4));
+        assert_eq!(value.load(), (3, 4));
+        assert_eq!(value.into_inner(), (3, 4));
+    }
+}
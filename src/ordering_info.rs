@@ -0,0 +1,60 @@
+//! Memory orderings the lock types use internally, exposed through their `ordering_info()`.
+use core::sync::atomic::Ordering;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct OrderingInfo {
+    /// Ordering of the successful lock acquisition (CAS, or swap for `FairSpinMutex`).
+    pub lock: Ordering,
+    /// Ordering of the store that unlocks.
+    pub unlock: Ordering,
+    /// Ordering of the version load (followed by an `Acquire` fence before validation) in optimistic reads.
+    /// `None` for locks without optimistic reads.
+    pub optimistic_read: Option<Ordering>,
+}
+impl OrderingInfo {
+    pub(crate) const LOCK: Self = Self {
+        lock: Ordering::Acquire,
+        unlock: Ordering::Release,
+        optimistic_read: None,
+    };
+    pub(crate) const FAIR: Self = Self {
+        lock: Ordering::AcqRel,
+        ..Self::LOCK
+    };
+    pub(crate) const SEQLOCK: Self = Self {
+        optimistic_read: Some(Ordering::Acquire),
+        ..Self::LOCK
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        atomic_spin_seqlock::SpinSeqLockAtomicPtr, fair_spin_mutex::FairSpinMutex,
+        spin_mutex::SpinMutex, spin_rwlock::SpinRwLock, spin_seqlock::SpinSeqLock,
+    };
+
+    // Usable in const context.
+    const SEQLOCK: OrderingInfo = SpinSeqLock::<u32>::ordering_info();
+
+    #[test]
+    fn per_lock_orderings() {
+        assert_eq!(SEQLOCK.optimistic_read, Some(Ordering::Acquire));
+        assert_eq!(
+            SpinSeqLockAtomicPtr::<u32>::ordering_info(),
+            OrderingInfo::SEQLOCK
+        );
+        for info in [
+            SpinMutex::<u32>::ordering_info(),
+            SpinRwLock::<u32>::ordering_info(),
+        ] {
+            assert_eq!(info.lock, Ordering::Acquire);
+            assert_eq!(info.unlock, Ordering::Release);
+            assert_eq!(info.optimistic_read, None);
+        }
+        let fair = FairSpinMutex::<u32>::ordering_info();
+        assert_eq!(fair.lock, Ordering::AcqRel);
+        assert_eq!(fair.unlock, Ordering::Release);
+    }
+}
@@ -0,0 +1,161 @@
+use core::{
+    ops::{Deref, DerefMut},
+    sync::atomic::{AtomicBool, Ordering},
+};
+use std::sync::{LockResult, PoisonError, TryLockError, TryLockResult};
+
+use crate::{
+    backoff::DEFAULT_SPIN_LIMIT,
+    spin_mutex::{SpinMutexEx, SpinMutexGuardEx},
+};
+
+pub type PoisoningSpinMutex<T> = PoisoningSpinMutexEx<DEFAULT_SPIN_LIMIT, T>;
+pub type PoisoningSpinMutexGuard<'a, T> = PoisoningSpinMutexGuardEx<'a, DEFAULT_SPIN_LIMIT, T>;
+
+/// `SpinMutex` that gets poisoned when a thread panics while holding it, same as `std::sync::Mutex`.
+///
+/// Locking a poisoned mutex still locks it, but returns the guard wrapped in a `PoisonError`.
+pub struct PoisoningSpinMutexEx<const S: isize, T> {
+    lock: SpinMutexEx<S, T>,
+    poisoned: AtomicBool,
+}
+pub struct PoisoningSpinMutexGuardEx<'a, const S: isize, T> {
+    guard: SpinMutexGuardEx<'a, S, T>,
+    poisoned: &'a AtomicBool,
+    /// Whether the thread was already panicking when it locked, so its panic is not this guard's fault.
+    panicking: bool,
+}
+impl<const S: isize, T> Drop for PoisoningSpinMutexGuardEx<'_, S, T> {
+    #[inline]
+    fn drop(&mut self) {
+        // `guard` field unlocks the mutex after this
+        if !self.panicking && std::thread::panicking() {
+            self.poisoned.store(true, Ordering::Relaxed);
+        }
+    }
+}
+impl<const S: isize, T> Deref for PoisoningSpinMutexGuardEx<'_, S, T> {
+    type Target = T;
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.guard
+    }
+}
+impl<const S: isize, T> DerefMut for PoisoningSpinMutexGuardEx<'_, S, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.guard
+    }
+}
+impl<const S: isize, T: Default> Default for PoisoningSpinMutexEx<S, T> {
+    #[inline]
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+impl<const S: isize, T> PoisoningSpinMutexEx<S, T> {
+    #[inline]
+    pub fn new(val: T) -> Self {
+        Self {
+            lock: SpinMutexEx::new(val),
+            poisoned: AtomicBool::new(false),
+        }
+    }
+    #[inline]
+    pub fn into_inner(self) -> LockResult<T> {
+        let poisoned = self.is_poisoned();
+        let data = self.lock.into_inner();
+        if poisoned {
+            Err(PoisonError::new(data))
+        } else {
+            Ok(data)
+        }
+    }
+    #[inline]
+    pub fn get_mut(&mut self) -> LockResult<&mut T> {
+        let poisoned = self.is_poisoned();
+        let data = self.lock.get_mut();
+        if poisoned {
+            Err(PoisonError::new(data))
+        } else {
+            Ok(data)
+        }
+    }
+    #[inline]
+    pub fn lock(&self) -> LockResult<PoisoningSpinMutexGuardEx<'_, S, T>> {
+        self.guard(self.lock.lock())
+    }
+    #[inline]
+    pub fn try_lock(&self) -> TryLockResult<PoisoningSpinMutexGuardEx<'_, S, T>> {
+        let guard = self.lock.try_lock().ok_or(TryLockError::WouldBlock)?;
+        Ok(self.guard(guard)?)
+    }
+    #[inline]
+    pub fn is_poisoned(&self) -> bool {
+        self.poisoned.load(Ordering::Relaxed)
+    }
+    #[inline]
+    pub fn clear_poison(&self) {
+        self.poisoned.store(false, Ordering::Relaxed);
+    }
+    #[inline]
+    fn guard<'a>(
+        &'a self,
+        guard: SpinMutexGuardEx<'a, S, T>,
+    ) -> LockResult<PoisoningSpinMutexGuardEx<'a, S, T>> {
+        let guard = PoisoningSpinMutexGuardEx {
+            guard,
+            poisoned: &self.poisoned,
+            panicking: std::thread::panicking(),
+        };
+        if self.is_poisoned() {
+            Err(PoisonError::new(guard))
+        } else {
+            Ok(guard)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn panic_poisons_for_other_threads() {
+        let mutex = PoisoningSpinMutex::new(0u32);
+        std::thread::scope(|s| {
+            let result = s
+                .spawn(|| {
+                    let mut guard = mutex.lock().unwrap();
+                    *guard = 1;
+                    panic!("poison it");
+                })
+                .join();
+            assert!(result.is_err());
+        });
+        assert!(mutex.is_poisoned());
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                let Err(poisoned) = mutex.lock() else {
+                    panic!("expected a poisoned lock");
+                };
+                let guard = poisoned.into_inner();
+                assert_eq!(*guard, 1);
+                assert!(matches!(mutex.try_lock(), Err(TryLockError::WouldBlock)));
+            });
+        });
+        assert!(matches!(mutex.try_lock(), Err(TryLockError::Poisoned(_))));
+
+        mutex.clear_poison();
+        assert_eq!(*mutex.lock().unwrap(), 1);
+        assert_eq!(mutex.into_inner().unwrap(), 1);
+    }
+
+    #[test]
+    fn no_panic_no_poison() {
+        let mutex = PoisoningSpinMutex::new(0u32);
+        *mutex.lock().unwrap() += 1;
+        assert!(!mutex.is_poisoned());
+        assert_eq!(*mutex.try_lock().unwrap(), 1);
+    }
+}
@@ -0,0 +1,79 @@
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use lock_api::{GuardSend, RawMutex};
+
+use crate::{
+    backoff::{Backoff, DEFAULT_SPIN_LIMIT},
+    contention_trace::ContentionTrace,
+};
+
+pub type RawSpinMutex = RawSpinMutexEx<DEFAULT_SPIN_LIMIT>;
+
+/// Data-less spin lock implementing `lock_api::RawMutex`, so it can be used as `lock_api::Mutex<RawSpinMutex, T>`.
+///
+/// Same locking as `SpinMutexEx`, but without the `lock-hierarchy` and `stats` features.
+pub struct RawSpinMutexEx<const S: isize> {
+    locked: AtomicBool,
+}
+unsafe impl<const S: isize> RawMutex for RawSpinMutexEx<S> {
+    const INIT: Self = Self {
+        locked: AtomicBool::new(false),
+    };
+    type GuardMarker = GuardSend;
+
+    #[inline]
+    fn lock(&self) {
+        let mut backoff = Backoff::<S>::new();
+        let mut trace = ContentionTrace::new();
+        loop {
+            if !self.locked.load(Ordering::Relaxed) && self.try_lock() {
+                trace.acquired::<Self>();
+                return;
+            }
+            trace.snoozed::<Self>();
+            backoff.snooze();
+        }
+    }
+    #[inline]
+    fn try_lock(&self) -> bool {
+        self.locked
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+    }
+    #[inline]
+    unsafe fn unlock(&self) {
+        self.locked.store(false, Ordering::Release);
+    }
+    #[inline]
+    fn is_locked(&self) -> bool {
+        self.locked.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Mutex<T> = lock_api::Mutex<RawSpinMutex, T>;
+
+    #[test]
+    fn concurrent_locks() {
+        let mutex = Mutex::new(0usize);
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    for _ in 0..1000 {
+                        *mutex.lock() += 1;
+                    }
+                });
+            }
+        });
+        let guard = mutex.lock();
+        assert!(mutex.is_locked());
+        assert!(mutex.try_lock().is_none());
+        assert_eq!(*guard, 4000);
+        drop(guard);
+        assert!(!mutex.is_locked());
+        assert_eq!(mutex.into_inner(), 4000);
+    }
+}
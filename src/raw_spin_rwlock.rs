@@ -0,0 +1,212 @@
+use core::sync::atomic::{AtomicIsize, Ordering};
+
+use lock_api::{
+    GuardSend, RawRwLock, RawRwLockDowngrade, RawRwLockUpgrade, RawRwLockUpgradeDowngrade,
+};
+
+use crate::{
+    backoff::{Backoff, DEFAULT_SPIN_LIMIT},
+    contention_trace::ContentionTrace,
+    spin_rwlock::{
+        SPIN_RW_LOCK_LOCKED, SPIN_RW_LOCK_READERS_MASK, SPIN_RW_LOCK_UNLOCKED,
+        SPIN_RW_LOCK_UPGRADEABLE, SPIN_RW_LOCK_UPGRADING,
+    },
+};
+
+pub type RawSpinRwLock = RawSpinRwLockEx<DEFAULT_SPIN_LIMIT>;
+
+/// Data-less spin rwlock implementing the `lock_api::RawRwLock` traits (including upgradable and downgrade
+/// support), so it can be used as `lock_api::RwLock<RawSpinRwLock, T>`.
+///
+/// Same locking (and state) as `SpinRwLockEx` with the default params: reader preference, and the reader
+/// count capped at `2^(isize::BITS - 3) - 1`, so it can't overflow into the upgradable bits. But without the
+/// `lock-hierarchy` and `stats` features.
+pub struct RawSpinRwLockEx<const S: isize> {
+    state: AtomicIsize,
+}
+impl<const S: isize> RawSpinRwLockEx<S> {
+    #[inline]
+    fn can_read(current: isize) -> bool {
+        current != SPIN_RW_LOCK_LOCKED
+            && current & SPIN_RW_LOCK_UPGRADING == 0
+            && current & SPIN_RW_LOCK_READERS_MASK < SPIN_RW_LOCK_READERS_MASK
+    }
+    /// CASes `current` to `f(current)` while `cond(current)` holds. Returns `false` once it doesn't.
+    #[inline]
+    fn try_update(&self, cond: impl Fn(isize) -> bool, f: impl Fn(isize) -> isize) -> bool {
+        let mut current = self.state.load(Ordering::Relaxed);
+        while cond(current) {
+            match self.state.compare_exchange_weak(
+                current,
+                f(current),
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return true,
+                Err(prev) => current = prev,
+            }
+        }
+        false
+    }
+    #[inline]
+    fn spin_until(&self, mut try_lock: impl FnMut(&Self) -> bool) {
+        let mut backoff = Backoff::<S>::new();
+        let mut trace = ContentionTrace::new();
+        while !try_lock(self) {
+            trace.snoozed::<Self>();
+            backoff.snooze();
+        }
+        trace.acquired::<Self>();
+    }
+}
+unsafe impl<const S: isize> RawRwLock for RawSpinRwLockEx<S> {
+    const INIT: Self = Self {
+        state: AtomicIsize::new(SPIN_RW_LOCK_UNLOCKED),
+    };
+    type GuardMarker = GuardSend;
+
+    #[inline]
+    fn lock_shared(&self) {
+        self.spin_until(Self::try_lock_shared);
+    }
+    #[inline]
+    fn try_lock_shared(&self) -> bool {
+        self.try_update(Self::can_read, |current| current + 1)
+    }
+    #[inline]
+    unsafe fn unlock_shared(&self) {
+        self.state.fetch_sub(1, Ordering::Release);
+    }
+    #[inline]
+    fn lock_exclusive(&self) {
+        self.spin_until(Self::try_lock_exclusive);
+    }
+    #[inline]
+    fn try_lock_exclusive(&self) -> bool {
+        self.state.load(Ordering::Relaxed) == SPIN_RW_LOCK_UNLOCKED
+            && self
+                .state
+                .compare_exchange(
+                    SPIN_RW_LOCK_UNLOCKED,
+                    SPIN_RW_LOCK_LOCKED,
+                    Ordering::Acquire,
+                    Ordering::Relaxed,
+                )
+                .is_ok()
+    }
+    #[inline]
+    unsafe fn unlock_exclusive(&self) {
+        self.state.store(SPIN_RW_LOCK_UNLOCKED, Ordering::Release);
+    }
+    #[inline]
+    fn is_locked(&self) -> bool {
+        self.state.load(Ordering::Relaxed) != SPIN_RW_LOCK_UNLOCKED
+    }
+    #[inline]
+    fn is_locked_exclusive(&self) -> bool {
+        self.state.load(Ordering::Relaxed) == SPIN_RW_LOCK_LOCKED
+    }
+}
+unsafe impl<const S: isize> RawRwLockDowngrade for RawSpinRwLockEx<S> {
+    #[inline]
+    unsafe fn downgrade(&self) {
+        self.state
+            .store(SPIN_RW_LOCK_UNLOCKED + 1, Ordering::Release);
+    }
+}
+unsafe impl<const S: isize> RawRwLockUpgrade for RawSpinRwLockEx<S> {
+    #[inline]
+    fn lock_upgradable(&self) {
+        self.spin_until(Self::try_lock_upgradable);
+    }
+    #[inline]
+    fn try_lock_upgradable(&self) -> bool {
+        self.try_update(
+            |current| current != SPIN_RW_LOCK_LOCKED && current & SPIN_RW_LOCK_UPGRADEABLE == 0,
+            |current| current | SPIN_RW_LOCK_UPGRADEABLE,
+        )
+    }
+    #[inline]
+    unsafe fn unlock_upgradable(&self) {
+        self.state
+            .fetch_sub(SPIN_RW_LOCK_UPGRADEABLE, Ordering::Release);
+    }
+    #[inline]
+    unsafe fn upgrade(&self) {
+        self.state
+            .fetch_add(SPIN_RW_LOCK_UPGRADING, Ordering::Relaxed);
+        self.spin_until(|lock| {
+            lock.state
+                .compare_exchange(
+                    SPIN_RW_LOCK_UPGRADEABLE | SPIN_RW_LOCK_UPGRADING,
+                    SPIN_RW_LOCK_LOCKED,
+                    Ordering::Acquire,
+                    Ordering::Relaxed,
+                )
+                .is_ok()
+        });
+    }
+    #[inline]
+    unsafe fn try_upgrade(&self) -> bool {
+        self.state
+            .compare_exchange(
+                SPIN_RW_LOCK_UPGRADEABLE,
+                SPIN_RW_LOCK_LOCKED,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            )
+            .is_ok()
+    }
+}
+unsafe impl<const S: isize> RawRwLockUpgradeDowngrade for RawSpinRwLockEx<S> {
+    #[inline]
+    unsafe fn downgrade_upgradable(&self) {
+        self.state
+            .fetch_add(1 - SPIN_RW_LOCK_UPGRADEABLE, Ordering::Release);
+    }
+    #[inline]
+    unsafe fn downgrade_to_upgradable(&self) {
+        self.state
+            .store(SPIN_RW_LOCK_UPGRADEABLE, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type RwLock<T> = lock_api::RwLock<RawSpinRwLock, T>;
+
+    #[test]
+    fn concurrent_reads() {
+        let lock = RwLock::new(5);
+        let first = lock.read();
+        std::thread::scope(|s| {
+            let readers: Vec<_> = (0..4).map(|_| s.spawn(|| *lock.read())).collect();
+            for reader in readers {
+                assert_eq!(reader.join().unwrap(), 5);
+            }
+        });
+        assert!(lock.try_write().is_none());
+        drop(first);
+        *lock.write() += 1;
+        assert_eq!(*lock.read(), 6);
+    }
+
+    #[test]
+    fn upgradable_and_downgrade() {
+        let lock = RwLock::new(0);
+        let upgradable = lock.upgradable_read();
+        assert!(lock.try_read().is_some());
+        assert!(lock.try_upgradable_read().is_none());
+        let mut write = lock_api::RwLockUpgradableReadGuard::upgrade(upgradable);
+        *write = 1;
+        assert!(lock.try_read().is_none());
+        let read = lock_api::RwLockWriteGuard::downgrade(write);
+        assert_eq!(*read, 1);
+        assert!(lock.try_read().is_some());
+        assert!(lock.try_write().is_none());
+        drop(read);
+        assert!(!lock.is_locked());
+    }
+}
@@ -0,0 +1,132 @@
+use core::{
+    cell::UnsafeCell,
+    mem::{self, MaybeUninit},
+    ptr,
+    sync::atomic::{AtomicUsize, Ordering, fence},
+};
+
+use crate::backoff::{Backoff, DEFAULT_SPIN_LIMIT};
+
+pub type SeqCell<T> = SeqCellEx<DEFAULT_SPIN_LIMIT, T>;
+
+/// Single-writer, multi-reader seqlock.
+///
+/// Since there is only one writer, it does not need to lock anything: `store` just makes the version odd,
+/// writes the value, and makes the version even again. Readers spin while the version is odd, and retry if
+/// it changed while they were reading.
+///
+/// With debug assertions enabled, concurrent `store`s panic.
+pub struct SeqCellEx<const B: isize, T: Copy> {
+    data: UnsafeCell<T>,
+    version: AtomicUsize,
+    #[cfg(debug_assertions)]
+    writing: core::sync::atomic::AtomicBool,
+}
+impl<const B: isize, T: Copy> SeqCellEx<B, T> {
+    #[inline]
+    pub const fn new(val: T) -> Self {
+        Self {
+            data: UnsafeCell::new(val),
+            version: AtomicUsize::new(0),
+            #[cfg(debug_assertions)]
+            writing: core::sync::atomic::AtomicBool::new(false),
+        }
+    }
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+    #[inline]
+    pub fn load(&self) -> T {
+        let mut backoff = Backoff::<B>::new();
+        loop {
+            let version = self.version.load(Ordering::Acquire);
+            if version.is_multiple_of(2) {
+                // Same as in `SpinSeqLockEx::optimistic_read`: the read may race with the writer, in which case
+                // the version check below discards it.
+                let data = if mem::size_of::<T>() == 0 {
+                    MaybeUninit::uninit()
+                } else {
+                    unsafe { ptr::read_volatile(self.data.get().cast::<MaybeUninit<T>>()) }
+                };
+                fence(Ordering::Acquire);
+                if self.version.load(Ordering::Relaxed) == version {
+                    // Safety: version did not change, so there were no writes while we were reading the value.
+                    return unsafe { data.assume_init() };
+                }
+            }
+            backoff.snooze();
+        }
+    }
+    /// # Safety
+    /// There must be at most one thread calling `store` at any time. Checked with debug assertions enabled.
+    #[inline]
+    pub unsafe fn store(&self, val: T) {
+        #[cfg(debug_assertions)]
+        assert!(
+            !self.writing.swap(true, Ordering::Acquire),
+            "SeqCell::store called concurrently from multiple writers"
+        );
+        let version = self.version.load(Ordering::Relaxed);
+        self.version
+            .store(version.wrapping_add(1), Ordering::Relaxed);
+        fence(Ordering::Release);
+        // Safety: we are the only writer, readers only use the value if the version did not change
+        unsafe { ptr::write_volatile(self.data.get(), val) };
+        self.version
+            .store(version.wrapping_add(2), Ordering::Release);
+        #[cfg(debug_assertions)]
+        self.writing.store(false, Ordering::Release);
+    }
+}
+impl<const B: isize, T: Copy + Default> Default for SeqCellEx<B, T> {
+    #[inline]
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+impl<const B: isize, T: Copy + core::fmt::Debug> core::fmt::Debug for SeqCellEx<B, T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("SeqCell")
+            .field("data", &self.load())
+            .finish()
+    }
+}
+unsafe impl<const B: isize, T: Copy + Send> Send for SeqCellEx<B, T> {}
+unsafe impl<const B: isize, T: Copy + Send> Sync for SeqCellEx<B, T> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg_attr(miri, ignore = "reads race with the writer, which miri reports as UB")]
+    fn readers_never_see_torn_values() {
+        const WRITES: u64 = 5000;
+        let cell = SeqCell::new([0u64; 8]);
+        std::thread::scope(|s| {
+            for _ in 0..3 {
+                s.spawn(|| {
+                    let mut last = 0;
+                    while last < WRITES {
+                        let value = cell.load();
+                        assert!(value.iter().all(|&v| v == value[0]), "torn read: {value:?}");
+                        assert!(value[0] >= last);
+                        last = value[0];
+                    }
+                });
+            }
+            s.spawn(|| {
+                for i in 1..=WRITES {
+                    // Safety: this is the only writer
+                    unsafe { cell.store([i; 8]) };
+                }
+            });
+        });
+        assert_eq!(cell.into_inner(), [WRITES; 8]);
+    }
+}
@@ -0,0 +1,113 @@
+use crate::{backoff::DEFAULT_SPIN_LIMIT, spin_seqlock::SpinSeqLockEx, sync::loom_const_fn};
+
+pub type SeqLockCache<K, V> = SeqLockCacheEx<DEFAULT_SPIN_LIMIT, K, V>;
+
+/// One-entry memoization cell, for hot single-key lookups (e.g. last used translation).
+///
+/// Hits are served with an optimistic read of the stored `(key, value)`. On a miss, the value is recomputed and
+/// stored under the write lock.
+pub struct SeqLockCacheEx<const B: isize, K: Copy, V: Copy> {
+    entry: SpinSeqLockEx<B, Option<(K, V)>>,
+}
+impl<const B: isize, K: Copy, V: Copy> SeqLockCacheEx<B, K, V> {
+    loom_const_fn! {
+        #[inline]
+        pub fn new() -> Self {
+            Self {
+                entry: SpinSeqLockEx::new(None),
+            }
+        }
+    }
+    /// Clears the cached entry.
+    #[inline]
+    pub fn invalidate(&self) {
+        self.entry.store(None);
+    }
+}
+impl<const B: isize, K: Copy + PartialEq, V: Copy> SeqLockCacheEx<B, K, V> {
+    /// Returns the cached value, if it was computed for `key`.
+    #[inline]
+    pub fn get(&self, key: K) -> Option<V> {
+        match self.entry.load() {
+            Some((k, v)) if k == key => Some(v),
+            _ => None,
+        }
+    }
+    /// Returns the cached value if it was computed for `key`, otherwise computes it with `f` and caches it.
+    ///
+    /// `f` is called under the write lock, so concurrent misses for the same key compute it only once.
+    #[inline]
+    pub fn get_or_compute(&self, key: K, f: impl FnOnce(K) -> V) -> V {
+        if let Some(v) = self.get(key) {
+            return v;
+        }
+        let mut guard = self.entry.write();
+        if let Some((k, v)) = *guard
+            && k == key
+        {
+            return v;
+        }
+        let v = f(key);
+        *guard = Some((key, v));
+        v
+    }
+}
+impl<const B: isize, K: Copy, V: Copy> Default for SeqLockCacheEx<B, K, V> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl<const B: isize, K: Copy + core::fmt::Debug, V: Copy + core::fmt::Debug> core::fmt::Debug
+    for SeqLockCacheEx<B, K, V>
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("SeqLockCache")
+            .field("entry", &self.entry.load())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    #[test]
+    fn hit_and_miss() {
+        let cache = SeqLockCache::<u32, u64>::new();
+        let calls = AtomicUsize::new(0);
+        let compute = |k: u32| {
+            calls.fetch_add(1, Ordering::Relaxed);
+            k as u64 * 10
+        };
+        assert_eq!(cache.get(1), None);
+        assert_eq!(cache.get_or_compute(1, compute), 10);
+        assert_eq!(cache.get_or_compute(1, compute), 10);
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+        assert_eq!(cache.get_or_compute(2, compute), 20);
+        assert_eq!(cache.get(1), None);
+        assert_eq!(calls.load(Ordering::Relaxed), 2);
+        cache.invalidate();
+        assert_eq!(cache.get(2), None);
+    }
+
+    #[test]
+    fn concurrent_recompute_once() {
+        let cache = SeqLockCache::<u32, u64>::new();
+        let calls = AtomicUsize::new(0);
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    let v = cache.get_or_compute(7, |k| {
+                        calls.fetch_add(1, Ordering::Relaxed);
+                        k as u64 + 1
+                    });
+                    assert_eq!(v, 8);
+                });
+            }
+        });
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+    }
+}
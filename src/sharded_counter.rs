@@ -0,0 +1,101 @@
+use core::{
+    cell::Cell,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::align::Align128;
+
+static NEXT_SHARD: AtomicUsize = AtomicUsize::new(0);
+std::thread_local! {
+    static THREAD_SHARD: Cell<usize> = const { Cell::new(usize::MAX) };
+}
+/// Threads get consecutive indices on their first `add`, so up to `SHARDS` threads never share a shard.
+fn current_thread_shard() -> usize {
+    THREAD_SHARD.with(|shard| {
+        if shard.get() == usize::MAX {
+            shard.set(NEXT_SHARD.fetch_add(1, Ordering::Relaxed));
+        }
+        shard.get()
+    })
+}
+
+struct Shard {
+    count: AtomicUsize,
+    _align: [Align128; 0],
+}
+
+/// Counter split into `SHARDS` cache-line-aligned atomics. Each thread adds to its own shard, so increments
+/// from different threads don't contend on a single cache line.
+///
+/// Reading it (`sum`) has to visit every shard, so it is meant for write-heavy counters (statistics etc.).
+pub struct ShardedCounter<const SHARDS: usize> {
+    shards: [Shard; SHARDS],
+}
+impl<const SHARDS: usize> ShardedCounter<SHARDS> {
+    #[inline]
+    pub const fn new() -> Self {
+        const { assert!(SHARDS > 0, "ShardedCounter needs at least one shard") };
+        Self {
+            shards: [const {
+                Shard {
+                    count: AtomicUsize::new(0),
+                    _align: [],
+                }
+            }; SHARDS],
+        }
+    }
+    /// Wrapping add to the current thread's shard.
+    #[inline]
+    pub fn add(&self, n: usize) {
+        self.shards[current_thread_shard() % SHARDS]
+            .count
+            .fetch_add(n, Ordering::Relaxed);
+    }
+    /// Wrapping sum of all shards.
+    ///
+    /// Shards are read one by one, so with concurrent `add`s the result is not a snapshot of a single point in
+    /// time. It includes every `add` that happened before the call though.
+    #[inline]
+    pub fn sum(&self) -> usize {
+        self.shards.iter().fold(0, |sum, shard| {
+            sum.wrapping_add(shard.count.load(Ordering::Relaxed))
+        })
+    }
+}
+impl<const SHARDS: usize> Default for ShardedCounter<SHARDS> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl<const SHARDS: usize> core::fmt::Debug for ShardedCounter<SHARDS> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("ShardedCounter")
+            .field("sum", &self.sum())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sum_equals_total_increments() {
+        const THREADS: usize = 6;
+        const ADDS: usize = 10_000;
+        // Fewer shards than threads, so some threads share one
+        let counter = ShardedCounter::<4>::new();
+        std::thread::scope(|s| {
+            for t in 0..THREADS {
+                let counter = &counter;
+                s.spawn(move || {
+                    for _ in 0..ADDS {
+                        counter.add(t + 1);
+                    }
+                });
+            }
+        });
+        assert_eq!(counter.sum(), ADDS * THREADS * (THREADS + 1) / 2);
+    }
+}
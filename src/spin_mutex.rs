@@ -1,29 +1,46 @@
 use core::{
     cell::UnsafeCell,
+    mem,
     ops::{Deref, DerefMut},
-    sync::atomic::{AtomicBool, Ordering},
+    ptr,
+    sync::atomic::Ordering,
 };
 
-use crate::backoff::{Backoff, DEFAULT_SPIN_LIMIT};
+use crate::{
+    backoff::{Backoff, DEFAULT_SPIN_LIMIT},
+    contention_trace::ContentionTrace,
+    hold_stats::{HoldStats, HoldTimer},
+    lock_hierarchy::LockLevel,
+    ordering_info::OrderingInfo,
+    sync::{AtomicBool, loom_const_fn},
+};
 
 pub type SpinMutex<T> = SpinMutexEx<DEFAULT_SPIN_LIMIT, T>;
 pub type SpinMutexGuard<'a, T> = SpinMutexGuardEx<'a, DEFAULT_SPIN_LIMIT, T>;
+pub type SpinMutexCooperativeGuard<'a, T> = SpinMutexCooperativeGuardEx<'a, DEFAULT_SPIN_LIMIT, T>;
+pub type SpinMutexMappedGuard<'a, T, U> = SpinMutexMappedGuardEx<'a, DEFAULT_SPIN_LIMIT, T, U>;
 
-pub struct SpinMutexEx<const S: isize, T> {
+/// `A` sets the alignment of the lock, see [`align`](crate::align).
+pub struct SpinMutexEx<const S: isize, T, A = ()> {
     data: UnsafeCell<T>,
     locked: AtomicBool,
+    level: LockLevel,
+    stats: HoldStats,
+    _align: [A; 0],
 }
-#[repr(transparent)]
-pub struct SpinMutexGuardEx<'a, const S: isize, T> {
-    lock: &'a SpinMutexEx<S, T>,
+#[cfg_attr(not(feature = "stats"), repr(transparent))]
+pub struct SpinMutexGuardEx<'a, const S: isize, T, A = ()> {
+    lock: &'a SpinMutexEx<S, T, A>,
+    timer: HoldTimer,
 }
-impl<const S: isize, T> Drop for SpinMutexGuardEx<'_, S, T> {
+impl<const S: isize, T, A> Drop for SpinMutexGuardEx<'_, S, T, A> {
     #[inline]
     fn drop(&mut self) {
-        self.lock.locked.store(false, Ordering::Release);
+        self.lock.stats.stop(&self.timer);
+        self.lock.release();
     }
 }
-impl<const S: isize, T> Deref for SpinMutexGuardEx<'_, S, T> {
+impl<const S: isize, T, A> Deref for SpinMutexGuardEx<'_, S, T, A> {
     type Target = T;
     #[inline]
     fn deref(&self) -> &Self::Target {
@@ -31,48 +48,330 @@ impl<const S: isize, T> Deref for SpinMutexGuardEx<'_, S, T> {
         unsafe { &*self.lock.data.get() }
     }
 }
-impl<const S: isize, T: Default> Default for SpinMutexEx<S, T> {
+impl<'a, const S: isize, T, A> SpinMutexGuardEx<'a, S, T, A> {
+    /// Projects the guard to a part of the locked data, e.g. one of its fields. The lock stays held until the
+    /// returned guard is dropped.
+    #[inline]
+    pub fn map<U>(
+        mut guard: Self,
+        f: impl FnOnce(&mut T) -> &mut U,
+    ) -> SpinMutexMappedGuardEx<'a, S, T, U, A> {
+        // `f` runs through the live guard, so the lock is released if it panics
+        let data = ptr::from_mut(f(&mut guard));
+        let lock = guard.lock;
+        // Safety: `guard` is forgotten right after, so the timer is moved out only once
+        let timer = unsafe { ptr::read(&guard.timer) };
+        mem::forget(guard);
+        SpinMutexMappedGuardEx { lock, data, timer }
+    }
+    /// Leaks the guard, so the mutex stays locked forever, and returns a reference to the data that lives as
+    /// long as the mutex (`'static` for a mutex in a `static`). Same as `std`'s `MutexGuard::leak`.
+    ///
+    /// With `lock-hierarchy` feature, the lock stays on this thread's held-locks stack.
+    #[inline]
+    pub fn leak(guard: Self) -> &'a mut T {
+        let lock = guard.lock;
+        mem::forget(guard);
+        // Safety: the lock is never unlocked, so nobody else can access the data anymore
+        unsafe { &mut *lock.data.get() }
+    }
+}
+/// Guard returned by [`SpinMutexGuardEx::map`].
+pub struct SpinMutexMappedGuardEx<'a, const S: isize, T, U, A = ()> {
+    lock: &'a SpinMutexEx<S, T, A>,
+    data: *mut U,
+    timer: HoldTimer,
+}
+impl<const S: isize, T, U, A> Drop for SpinMutexMappedGuardEx<'_, S, T, U, A> {
+    #[inline]
+    fn drop(&mut self) {
+        self.lock.stats.stop(&self.timer);
+        self.lock.release();
+    }
+}
+impl<const S: isize, T, U, A> Deref for SpinMutexMappedGuardEx<'_, S, T, U, A> {
+    type Target = U;
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        // Safety: `data` points into the locked data, and we hold the lock
+        unsafe { &*self.data }
+    }
+}
+impl<const S: isize, T, U, A> DerefMut for SpinMutexMappedGuardEx<'_, S, T, U, A> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // Safety: `data` points into the locked data, and we hold the lock
+        unsafe { &mut *self.data }
+    }
+}
+unsafe impl<const S: isize, T: Send, U: Sync, A> Sync for SpinMutexMappedGuardEx<'_, S, T, U, A> {}
+/// Guard returned by `lock_cooperative`. Call [`tick`](Self::tick) from a long critical section: every
+/// `max_hold` ticks the lock is released and reacquired, so waiters can get in.
+pub struct SpinMutexCooperativeGuardEx<'a, const S: isize, T, A = ()> {
+    lock: &'a SpinMutexEx<S, T, A>,
+    max_hold: usize,
+    ticks: usize,
+    timer: HoldTimer,
+}
+impl<const S: isize, T, A> SpinMutexCooperativeGuardEx<'_, S, T, A> {
+    /// Counts a tick, and returns `true` if the lock was released and reacquired.
+    ///
+    /// After releasing, it waits (for a short spin phase, and one `yield_now` with `std`) until another thread
+    /// takes the lock, before reacquiring it. The lock has no waiter queue, so the handoff is best-effort: a
+    /// waiter that is still sleeping in its backoff can miss the window, and without `std` (no yield) a
+    /// waiter on the same core only gets in if it's scheduled during the spin phase.
+    ///
+    /// Other threads may have modified the data when this returns `true`.
+    #[inline]
+    pub fn tick(&mut self) -> bool {
+        self.ticks += 1;
+        if self.ticks < self.max_hold {
+            return false;
+        }
+        self.ticks = 0;
+        self.lock.stats.stop(&self.timer);
+        self.lock.release();
+        self.wait_for_handoff();
+        self.lock.acquire();
+        self.timer = self.lock.stats.start();
+        true
+    }
+    #[inline]
+    fn wait_for_handoff(&self) {
+        let mut backoff = Backoff::<DEFAULT_SPIN_LIMIT>::new();
+        while !self.lock.is_locked() && !backoff.is_completed() {
+            backoff.snooze();
+        }
+        #[cfg(feature = "std")]
+        if !self.lock.is_locked() {
+            crate::sync::yield_now();
+        }
+    }
+}
+impl<const S: isize, T, A> Drop for SpinMutexCooperativeGuardEx<'_, S, T, A> {
+    #[inline]
+    fn drop(&mut self) {
+        self.lock.stats.stop(&self.timer);
+        self.lock.release();
+    }
+}
+impl<const S: isize, T, A> Deref for SpinMutexCooperativeGuardEx<'_, S, T, A> {
+    type Target = T;
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        // Safety: safe to deref while we hold the lock
+        unsafe { &*self.lock.data.get() }
+    }
+}
+impl<const S: isize, T, A> DerefMut for SpinMutexCooperativeGuardEx<'_, S, T, A> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // Safety: safe to deref while we hold the lock
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+impl<const S: isize, T: Default, A> Default for SpinMutexEx<S, T, A> {
     #[inline]
     fn default() -> Self {
         Self {
             data: UnsafeCell::new(T::default()),
             locked: AtomicBool::new(false),
+            level: LockLevel::NONE,
+            stats: HoldStats::new(),
+            _align: [],
         }
     }
 }
-impl<const S: isize, T> DerefMut for SpinMutexGuardEx<'_, S, T> {
+impl<const S: isize, T, A> DerefMut for SpinMutexGuardEx<'_, S, T, A> {
     #[inline]
     fn deref_mut(&mut self) -> &mut Self::Target {
         // Safety: safe to deref while we hold the write lock
         unsafe { &mut *self.lock.data.get() }
     }
 }
-impl<const S: isize, T: core::fmt::Debug> core::fmt::Debug for SpinMutexEx<S, T> {
+impl<const S: isize, T: core::fmt::Debug, A> core::fmt::Debug for SpinMutexEx<S, T, A> {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         f.debug_struct("SpinLock")
             .field("data", &self.data.get())
             .finish()
     }
 }
-impl<const S: isize, T> SpinMutexEx<S, T> {
+impl<const S: isize, T, A> SpinMutexEx<S, T, A> {
+    loom_const_fn! {
+        #[inline]
+        pub fn new(val: T) -> Self {
+            Self {
+                data: UnsafeCell::new(val),
+                locked: AtomicBool::new(false),
+                level: LockLevel::NONE,
+                stats: HoldStats::new(),
+                _align: [],
+            }
+        }
+    }
+    /// Initializes the lock directly in `slot`, without moving the whole lock (e.g. for a large `T` in
+    /// preallocated memory).
+    ///
+    /// # Safety
+    /// `slot` must be valid for writes and properly aligned for `Self`. Previous contents are overwritten
+    /// without being dropped.
     #[inline]
-    pub fn new(val: T) -> Self {
-        Self {
-            data: UnsafeCell::new(val),
-            locked: AtomicBool::new(false),
+    pub unsafe fn init_in_place(slot: *mut Self, val: T) {
+        // Safety: guaranteed by the caller. `_align` is zero-sized, so it needs no initialization.
+        unsafe {
+            UnsafeCell::raw_get(&raw const (*slot).data).write(val);
+            (&raw mut (*slot).locked).write(AtomicBool::new(false));
+            (&raw mut (*slot).level).write(LockLevel::NONE);
+            (&raw mut (*slot).stats).write(HoldStats::new());
         }
     }
+    /// Assigns a level to this lock for the `lock-hierarchy` validation. No-op without the feature.
+    #[inline]
+    pub const fn with_level(mut self, level: usize) -> Self {
+        self.level = LockLevel::new(level);
+        self
+    }
+    /// Memory orderings used by this lock, see [`OrderingInfo`].
+    #[inline]
+    pub const fn ordering_info() -> OrderingInfo {
+        OrderingInfo::LOCK
+    }
     #[inline]
     pub fn into_inner(self) -> T {
         self.data.into_inner()
     }
+    /// `into_inner` for a mutex shared through an `Arc`. Returns the `Arc` back if it is not the only one.
+    #[cfg(feature = "alloc")]
+    #[inline]
+    pub fn try_into_inner(self: alloc::sync::Arc<Self>) -> Result<T, alloc::sync::Arc<Self>> {
+        alloc::sync::Arc::try_unwrap(self).map(Self::into_inner)
+    }
     #[inline]
     pub fn get_mut(&mut self) -> &mut T {
         self.data.get_mut()
     }
+    /// Whether the mutex is currently locked. The result may already be outdated when it's returned, so only
+    /// use it for diagnostics.
+    #[inline]
+    pub fn is_locked(&self) -> bool {
+        self.locked.load(Ordering::Relaxed)
+    }
+    #[inline]
+    pub fn lock(&self) -> SpinMutexGuardEx<'_, S, T, A> {
+        self.level.check();
+        self.acquire();
+        SpinMutexGuardEx {
+            lock: self,
+            timer: self.stats.start(),
+        }
+    }
+    /// Runs `callback` under the lock. The guard can't escape the closure, and is dropped when it returns (or
+    /// panics).
+    #[inline]
+    pub fn with_lock<R>(&self, callback: impl FnOnce(&mut T) -> R) -> R {
+        let mut guard = self.lock();
+        callback(&mut guard)
+    }
+    /// Builds a guard for a lock that is already held, e.g. one whose guard was `mem::forget`-ed to keep the
+    /// lock held across an FFI call. Dropping the returned guard unlocks the mutex.
+    ///
+    /// # Safety
+    /// The mutex must be locked, and the caller must be the logical owner of that lock: no other guard for
+    /// it may exist, and nobody else may unlock it.
+    #[inline]
+    pub unsafe fn guard_from_raw(&self) -> SpinMutexGuardEx<'_, S, T, A> {
+        debug_assert!(
+            self.is_locked(),
+            "guard_from_raw called on an unlocked mutex"
+        );
+        SpinMutexGuardEx {
+            lock: self,
+            timer: self.stats.start(),
+        }
+    }
+    /// Single attempt to lock the mutex, without spinning.
+    #[inline]
+    pub fn try_lock(&self) -> Option<SpinMutexGuardEx<'_, S, T, A>> {
+        self.level.check();
+        if self
+            .locked
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            return None;
+        }
+        self.level.acquired();
+        Some(SpinMutexGuardEx {
+            lock: self,
+            timer: self.stats.start(),
+        })
+    }
+    /// Locks the mutex, returning a guard that periodically lets waiters in, see
+    /// [`SpinMutexCooperativeGuardEx::tick`].
+    #[inline]
+    pub fn lock_cooperative(&self, max_hold: usize) -> SpinMutexCooperativeGuardEx<'_, S, T, A> {
+        self.level.check();
+        self.acquire();
+        SpinMutexCooperativeGuardEx {
+            lock: self,
+            max_hold,
+            ticks: 0,
+            timer: self.stats.start(),
+        }
+    }
+    /// Same as `lock`, but picks the backoff based on the recent contention: if `lock_adaptive` calls needed
+    /// more snoozes than the `S` spin phase on average, it yields the thread right away instead of spinning.
+    ///
+    /// Only `lock_adaptive` updates the average, see `avg_lock_snoozes`.
+    #[cfg(feature = "stats")]
+    #[inline]
+    pub fn lock_adaptive(&self) -> SpinMutexGuardEx<'_, S, T, A> {
+        self.level.check();
+        let snoozes = if self.adaptive_yields() {
+            self.acquire_with::<0>()
+        } else {
+            self.acquire_with::<S>()
+        };
+        self.stats.record_snoozes(snoozes);
+        SpinMutexGuardEx {
+            lock: self,
+            timer: self.stats.start(),
+        }
+    }
+    /// Whether `lock_adaptive` yields right away, instead of spinning first.
+    #[cfg(feature = "stats")]
+    #[inline]
+    fn adaptive_yields(&self) -> bool {
+        self.stats.avg_snoozes() > S.max(1) as usize
+    }
+    /// Moving average of the snoozes `lock_adaptive` needed to acquire the lock.
+    #[cfg(feature = "stats")]
+    #[inline]
+    pub fn avg_lock_snoozes(&self) -> usize {
+        self.stats.avg_snoozes()
+    }
+    /// Total time the lock was held, in nanoseconds.
+    #[cfg(feature = "stats")]
+    #[inline]
+    pub fn total_write_hold_nanos(&self) -> u64 {
+        self.stats.total_nanos()
+    }
+    /// Number of acquisitions that had to wait for the lock.
+    #[cfg(feature = "stats")]
     #[inline]
-    pub fn lock(&self) -> SpinMutexGuardEx<'_, S, T> {
-        let mut backoff = Backoff::<S>::new();
+    pub fn contended_write_acquisitions(&self) -> usize {
+        self.stats.contended()
+    }
+    #[inline]
+    fn acquire(&self) {
+        self.acquire_with::<S>();
+    }
+    /// Returns the number of snoozes it took to acquire the lock.
+    #[inline]
+    fn acquire_with<const B: isize>(&self) -> usize {
+        let mut backoff = Backoff::<B>::new();
+        let mut trace = ContentionTrace::new();
+        let mut snoozes = 0;
         loop {
             // if !self.locked.swap(true, Ordering::Acquire) {
             if !self.locked.load(Ordering::Relaxed)
@@ -81,11 +380,242 @@ impl<const S: isize, T> SpinMutexEx<S, T> {
                     .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
                     .is_ok()
             {
-                return SpinMutexGuardEx { lock: self };
+                self.level.acquired();
+                trace.acquired::<Self>();
+                if snoozes > 0 {
+                    self.stats.record_contended();
+                }
+                return snoozes;
             }
+            trace.snoozed::<Self>();
             backoff.snooze();
+            snoozes += 1;
+        }
+    }
+    #[inline]
+    fn release(&self) {
+        self.locked.store(false, Ordering::Release);
+        self.level.released();
+    }
+}
+unsafe impl<const S: isize, T: Send, A> Send for SpinMutexEx<S, T, A> {}
+unsafe impl<const S: isize, T: Send, A> Sync for SpinMutexEx<S, T, A> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cooperative_guard_releases_every_max_hold_ticks() {
+        let mutex = SpinMutex::new(0);
+        let mut guard = mutex.lock_cooperative(3);
+        let released: [bool; 6] = core::array::from_fn(|_| guard.tick());
+        assert_eq!(released, [false, false, true, false, false, true]);
+        assert!(mutex.is_locked());
+        drop(guard);
+        assert!(!mutex.is_locked());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn cooperative_guard_lets_waiter_in() {
+        use std::time::{Duration, Instant};
+
+        let mutex = SpinMutex::new(0);
+        let entered = AtomicBool::new(false);
+        std::thread::scope(|s| {
+            let mut guard = mutex.lock_cooperative(1);
+            s.spawn(|| {
+                *mutex.lock() += 1;
+                entered.store(true, Ordering::Release);
+            });
+            let deadline = Instant::now() + Duration::from_secs(10);
+            while !entered.load(Ordering::Acquire) && Instant::now() < deadline {
+                *guard += 10;
+                guard.tick();
+            }
+            assert!(entered.load(Ordering::Acquire), "waiter starved");
+            assert_eq!(*guard % 10, 1);
+        });
+    }
+
+    #[test]
+    fn try_lock_fails_while_locked() {
+        let mutex = SpinMutex::new(0u32);
+        let guard = mutex.try_lock().unwrap();
+        assert!(mutex.try_lock().is_none());
+        drop(guard);
+        *mutex.try_lock().unwrap() += 1;
+        assert_eq!(*mutex.lock(), 1);
+    }
+
+    #[test]
+    fn is_locked_transitions() {
+        let mutex = SpinMutex::new(0u32);
+        assert!(!mutex.is_locked());
+        let guard = mutex.lock();
+        assert!(mutex.is_locked());
+        drop(guard);
+        assert!(!mutex.is_locked());
+    }
+
+    #[test]
+    fn guard_from_raw_unlocks_on_drop() {
+        let mutex = SpinMutex::new(0u32);
+        // Keeps the lock held without a guard, like across an FFI call.
+        mem::forget(mutex.lock());
+        assert!(mutex.is_locked());
+        {
+            // Safety: the lock is held, and its original guard is gone
+            let mut guard = unsafe { mutex.guard_from_raw() };
+            *guard += 1;
+        }
+        assert!(!mutex.is_locked());
+        assert_eq!(*mutex.lock(), 1);
+    }
+
+    #[cfg(feature = "stats")]
+    #[test]
+    fn lock_adaptive_yields_under_sustained_contention() {
+        use std::time::Duration;
+
+        let mutex = SpinMutex::new(0u32);
+        *mutex.lock_adaptive() += 1;
+        assert!(!mutex.adaptive_yields());
+
+        for _ in 0..20 {
+            std::thread::scope(|s| {
+                let guard = mutex.lock();
+                s.spawn(|| *mutex.lock_adaptive() += 1);
+                std::thread::sleep(Duration::from_millis(2));
+                drop(guard);
+            });
+        }
+        assert!(mutex.avg_lock_snoozes() > DEFAULT_SPIN_LIMIT as usize);
+        assert!(mutex.adaptive_yields());
+
+        // Average decays once the contention is gone.
+        for _ in 0..100 {
+            *mutex.lock_adaptive() += 1;
+        }
+        assert!(!mutex.adaptive_yields());
+        assert_eq!(*mutex.lock(), 121);
+    }
+
+    #[test]
+    fn zero_sized_token() {
+        let token = SpinMutex::new(());
+        let counter = core::cell::Cell::new(0);
+        struct External<'a>(&'a core::cell::Cell<u32>);
+        // Safety: only accessed while holding `token`
+        unsafe impl Sync for External<'_> {}
+        let external = External(&counter);
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    for _ in 0..100 {
+                        let _guard = token.lock();
+                        let external = &external;
+                        external.0.set(external.0.get() + 1);
+                    }
+                });
+            }
+        });
+        assert_eq!(counter.get(), 400);
+        let guard = token.try_lock().unwrap();
+        assert!(token.try_lock().is_none());
+        assert_eq!(*guard, ());
+    }
+
+    #[test]
+    fn init_in_place_large() {
+        let mut slot = core::mem::MaybeUninit::<SpinMutex<[u8; 4096]>>::uninit();
+        // Safety: `slot` is valid for writes and aligned
+        unsafe { SpinMutex::init_in_place(slot.as_mut_ptr(), [7; 4096]) };
+        // Safety: fully initialized by `init_in_place`
+        let mutex = unsafe { slot.assume_init_ref() };
+        assert!(!mutex.is_locked());
+        mutex.lock()[4095] = 1;
+        let guard = mutex.lock();
+        assert_eq!(guard[0], 7);
+        assert_eq!(guard[4095], 1);
+    }
+
+    #[cfg(not(loom))]
+    #[test]
+    fn static_mutex() {
+        static M: SpinMutex<u32> = SpinMutex::new(0);
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    for _ in 0..100 {
+                        *M.lock() += 1;
+                    }
+                });
+            }
+        });
+        assert_eq!(*M.lock(), 400);
+    }
+
+    #[test]
+    fn with_lock_mutates_and_releases() {
+        let mutex = SpinMutex::new(vec![1u32]);
+        let len = mutex.with_lock(|v| {
+            assert!(mutex.is_locked());
+            v.push(2);
+            v.len()
+        });
+        assert_eq!(len, 2);
+        assert!(!mutex.is_locked());
+        assert_eq!(*mutex.try_lock().unwrap(), [1, 2]);
+    }
+
+    #[test]
+    fn stays_locked_after_leak() {
+        let mutex = SpinMutex::new(1);
+        let data = SpinMutexGuardEx::leak(mutex.lock());
+        *data = 2;
+        assert!(mutex.is_locked());
+        assert!(mutex.try_lock().is_none());
+        assert_eq!(*data, 2);
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn try_into_inner_arc() {
+        use std::sync::Arc;
+
+        let lock = Arc::new(SpinMutex::new(5u32));
+        let other = Arc::clone(&lock);
+        let Err(lock) = lock.try_into_inner() else {
+            panic!("shared Arc was unwrapped");
+        };
+        assert!(Arc::ptr_eq(&lock, &other));
+        drop(other);
+        assert_eq!(lock.try_into_inner().ok(), Some(5));
+    }
+
+    #[test]
+    fn map_projects_tuple_field() {
+        let mutex = SpinMutex::new((1u32, 2u64));
+        {
+            let mut second = SpinMutexGuardEx::map(mutex.lock(), |pair| &mut pair.1);
+            *second += 40;
+            assert!(mutex.is_locked());
+            assert!(mutex.try_lock().is_none());
         }
+        assert!(!mutex.is_locked());
+        assert_eq!(*mutex.lock(), (1, 42));
+    }
+
+    #[test]
+    fn map_releases_on_panic() {
+        let mutex = SpinMutex::new((1u32, 2u64));
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            SpinMutexGuardEx::map(mutex.lock(), |_| -> &mut u64 { panic!("map") });
+        }));
+        assert!(result.is_err());
+        assert!(!mutex.is_locked());
+        assert_eq!(*mutex.lock(), (1, 2));
     }
 }
-unsafe impl<const S: isize, T: Send> Send for SpinMutexEx<S, T> {}
-unsafe impl<const S: isize, T: Send> Sync for SpinMutexEx<S, T> {}
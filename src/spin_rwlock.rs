@@ -1,44 +1,213 @@
 use core::{
     cell::UnsafeCell,
+    mem,
     ops::{Deref, DerefMut},
-    sync::atomic::{AtomicIsize, Ordering},
+    sync::atomic::Ordering,
 };
 
-use crate::backoff::{Backoff, DEFAULT_SPIN_LIMIT};
+use crate::{
+    backoff::{Backoff, DEFAULT_SPIN_LIMIT},
+    contention_trace::ContentionTrace,
+    hold_stats::{HoldStats, HoldTimer},
+    lock_hierarchy::LockLevel,
+    ordering_info::OrderingInfo,
+    sync::{AtomicIsize, AtomicUsize, loom_const_fn},
+};
 
 pub type SpinRwLock<T> = SpinRwLockEx<DEFAULT_SPIN_LIMIT, T>;
 pub type SpinRwLockReadGuard<'a, T> = SpinRwLockReadGuardEx<'a, DEFAULT_SPIN_LIMIT, T>;
 pub type SpinRwLockWriteGuard<'a, T> = SpinRwLockWriteGuardEx<'a, DEFAULT_SPIN_LIMIT, T>;
+pub type SpinRwLockUpgradeableGuard<'a, T> =
+    SpinRwLockUpgradeableGuardEx<'a, DEFAULT_SPIN_LIMIT, T>;
+/// `SpinRwLock` where a waiting writer blocks new readers right away, see the `W` param of [`SpinRwLockEx`].
+pub type WriterPreferringSpinRwLock<T> = SpinRwLockEx<DEFAULT_SPIN_LIMIT, T, { isize::MAX }, (), 0>;
 
-const SPIN_RW_LOCK_LOCKED: isize = -1;
-const SPIN_RW_LOCK_UNLOCKED: isize = 0;
-pub struct SpinRwLockEx<const S: isize, T> {
+// `readers` state: -1 is write locked. Otherwise the low bits are the number of readers, and two high bits
+// mark an upgradeable guard being held, and that guard waiting to upgrade (no new readers get in).
+pub(crate) const SPIN_RW_LOCK_LOCKED: isize = -1;
+pub(crate) const SPIN_RW_LOCK_UNLOCKED: isize = 0;
+pub(crate) const SPIN_RW_LOCK_UPGRADEABLE: isize = 1 << (isize::BITS - 2);
+pub(crate) const SPIN_RW_LOCK_UPGRADING: isize = 1 << (isize::BITS - 3);
+pub(crate) const SPIN_RW_LOCK_READERS_MASK: isize = SPIN_RW_LOCK_UPGRADING - 1;
+/// Spinning reader-writer lock.
+///
+/// `R` caps the number of concurrent readers. Once the cap is reached, new readers spin until one of the
+/// current readers drops its guard, so the reader count can never overflow into the write-locked state, or
+/// into the upgradeable guard bits. Values above `2^(isize::BITS - 3) - 1` (including the default) are
+/// capped at that.
+///
+/// `W` is the writer preference threshold. Once a writer has been waiting for `W` backoff rounds, new readers
+/// wait until it acquires the lock. Default (`usize::MAX`) never blocks readers (reader preference), so a
+/// continuous stream of readers can starve writers. With a threshold, writers can't be starved, but a
+/// continuous stream of writers can starve readers instead. Also, a thread that already holds a read guard
+/// and takes another one can deadlock with a waiting writer.
+///
+/// `A` sets the alignment of the lock, see [`align`](crate::align).
+pub struct SpinRwLockEx<
+    const S: isize,
+    T,
+    const R: isize = { isize::MAX },
+    A = (),
+    const W: usize = { usize::MAX },
+> {
     data: UnsafeCell<T>,
     readers: AtomicIsize,
+    writers_waiting: AtomicUsize,
+    level: LockLevel,
+    stats: HoldStats,
+    _align: [A; 0],
 }
 #[repr(transparent)]
-pub struct SpinRwLockReadGuardEx<'a, const S: isize, T> {
-    lock: &'a SpinRwLockEx<S, T>,
+pub struct SpinRwLockReadGuardEx<
+    'a,
+    const S: isize,
+    T,
+    const R: isize = { isize::MAX },
+    A = (),
+    const W: usize = { usize::MAX },
+> {
+    lock: &'a SpinRwLockEx<S, T, R, A, W>,
 }
+/// Read guard that can be upgraded to a write guard, see [`SpinRwLockEx::upgradeable_read`].
 #[repr(transparent)]
-pub struct SpinRwLockWriteGuardEx<'a, const S: isize, T> {
-    lock: &'a SpinRwLockEx<S, T>,
+pub struct SpinRwLockUpgradeableGuardEx<
+    'a,
+    const S: isize,
+    T,
+    const R: isize = { isize::MAX },
+    A = (),
+    const W: usize = { usize::MAX },
+> {
+    lock: &'a SpinRwLockEx<S, T, R, A, W>,
+}
+#[cfg_attr(not(feature = "stats"), repr(transparent))]
+pub struct SpinRwLockWriteGuardEx<
+    'a,
+    const S: isize,
+    T,
+    const R: isize = { isize::MAX },
+    A = (),
+    const W: usize = { usize::MAX },
+> {
+    lock: &'a SpinRwLockEx<S, T, R, A, W>,
+    timer: HoldTimer,
 }
-impl<const S: isize, T> Drop for SpinRwLockReadGuardEx<'_, S, T> {
+impl<const S: isize, T, const R: isize, A, const W: usize> Drop
+    for SpinRwLockReadGuardEx<'_, S, T, R, A, W>
+{
     #[inline]
     fn drop(&mut self) {
         self.lock.readers.fetch_sub(1, Ordering::Release);
+        self.lock.level.released();
     }
 }
-impl<const S: isize, T> Drop for SpinRwLockWriteGuardEx<'_, S, T> {
+impl<const S: isize, T, const R: isize, A, const W: usize> Drop
+    for SpinRwLockUpgradeableGuardEx<'_, S, T, R, A, W>
+{
     #[inline]
     fn drop(&mut self) {
+        self.lock
+            .readers
+            .fetch_sub(SPIN_RW_LOCK_UPGRADEABLE, Ordering::Release);
+        self.lock.level.released();
+    }
+}
+impl<const S: isize, T, const R: isize, A, const W: usize> Deref
+    for SpinRwLockUpgradeableGuardEx<'_, S, T, R, A, W>
+{
+    type Target = T;
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        // Safety: safe to deref while we hold the read lock
+        unsafe { &*self.lock.data.get() }
+    }
+}
+impl<'a, const S: isize, T, const R: isize, A, const W: usize>
+    SpinRwLockUpgradeableGuardEx<'a, S, T, R, A, W>
+{
+    /// Waits for the other readers to drop their guards, and turns this guard into a write guard, without
+    /// unlocking in between. New readers can't get in while it waits.
+    #[inline]
+    pub fn upgrade(self) -> SpinRwLockWriteGuardEx<'a, S, T, R, A, W> {
+        let lock = self.lock;
+        mem::forget(self);
+        lock.readers
+            .fetch_add(SPIN_RW_LOCK_UPGRADING, Ordering::Relaxed);
+        let mut backoff = Backoff::<S>::new();
+        let mut trace = ContentionTrace::new();
+        while lock
+            .readers
+            .compare_exchange(
+                SPIN_RW_LOCK_UPGRADEABLE | SPIN_RW_LOCK_UPGRADING,
+                SPIN_RW_LOCK_LOCKED,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            )
+            .is_err()
+        {
+            trace.snoozed::<SpinRwLockEx<S, T, R, A, W>>();
+            backoff.snooze();
+        }
+        trace.acquired::<SpinRwLockEx<S, T, R, A, W>>();
+        SpinRwLockWriteGuardEx {
+            lock,
+            timer: lock.stats.start(),
+        }
+    }
+    /// Upgrades, applies `f`, and downgrades to a plain read guard, see
+    /// [`SpinRwLockEx::update_and_keep_reading`].
+    #[inline]
+    pub fn update_and_keep_reading(
+        self,
+        f: impl FnOnce(&mut T),
+    ) -> SpinRwLockReadGuardEx<'a, S, T, R, A, W> {
+        let mut guard = self.upgrade();
+        f(&mut guard);
+        SpinRwLockWriteGuardEx::downgrade(guard)
+    }
+}
+impl<const S: isize, T, const R: isize, A, const W: usize> Drop
+    for SpinRwLockWriteGuardEx<'_, S, T, R, A, W>
+{
+    #[inline]
+    fn drop(&mut self) {
+        self.lock.stats.stop(&self.timer);
         self.lock
             .readers
             .store(SPIN_RW_LOCK_UNLOCKED, Ordering::Release);
+        self.lock.level.released();
     }
 }
-impl<const S: isize, T> Deref for SpinRwLockReadGuardEx<'_, S, T> {
+impl<'a, const S: isize, T, const R: isize, A, const W: usize>
+    SpinRwLockWriteGuardEx<'a, S, T, R, A, W>
+{
+    /// Turns the write lock into a read lock, without unlocking in between, so no writer can get in. Other
+    /// readers can lock it as soon as this returns.
+    #[inline]
+    pub fn downgrade(guard: Self) -> SpinRwLockReadGuardEx<'a, S, T, R, A, W> {
+        const { assert!(R > 0, "Max readers must be positive") };
+        let lock = guard.lock;
+        lock.stats.stop(&guard.timer);
+        mem::forget(guard);
+        lock.readers
+            .store(SPIN_RW_LOCK_UNLOCKED + 1, Ordering::Release);
+        SpinRwLockReadGuardEx { lock }
+    }
+    /// Leaks the guard, so the lock stays write locked forever, and returns a reference to the data that lives
+    /// as long as the lock. Same as `SpinMutexGuardEx::leak`.
+    ///
+    /// With `lock-hierarchy` feature, the lock stays on this thread's held-locks stack.
+    #[inline]
+    pub fn leak(guard: Self) -> &'a mut T {
+        let lock = guard.lock;
+        mem::forget(guard);
+        // Safety: the lock is never unlocked, so nobody else can access the data anymore
+        unsafe { &mut *lock.data.get() }
+    }
+}
+impl<const S: isize, T, const R: isize, A, const W: usize> Deref
+    for SpinRwLockReadGuardEx<'_, S, T, R, A, W>
+{
     type Target = T;
     #[inline]
     fn deref(&self) -> &Self::Target {
@@ -46,7 +215,9 @@ impl<const S: isize, T> Deref for SpinRwLockReadGuardEx<'_, S, T> {
         unsafe { &*self.lock.data.get() }
     }
 }
-impl<const S: isize, T> Deref for SpinRwLockWriteGuardEx<'_, S, T> {
+impl<const S: isize, T, const R: isize, A, const W: usize> Deref
+    for SpinRwLockWriteGuardEx<'_, S, T, R, A, W>
+{
     type Target = T;
     #[inline]
     fn deref(&self) -> &Self::Target {
@@ -54,32 +225,114 @@ impl<const S: isize, T> Deref for SpinRwLockWriteGuardEx<'_, S, T> {
         unsafe { &*self.lock.data.get() }
     }
 }
-impl<const S: isize, T> DerefMut for SpinRwLockWriteGuardEx<'_, S, T> {
+impl<const S: isize, T, const R: isize, A, const W: usize> DerefMut
+    for SpinRwLockWriteGuardEx<'_, S, T, R, A, W>
+{
     #[inline]
     fn deref_mut(&mut self) -> &mut Self::Target {
         // Safety: safe to deref while we hold the write lock
         unsafe { &mut *self.lock.data.get() }
     }
 }
-impl<const S: isize, T> SpinRwLockEx<S, T> {
+impl<const S: isize, T: Default, const R: isize, A, const W: usize> Default
+    for SpinRwLockEx<S, T, R, A, W>
+{
+    #[inline]
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+impl<const S: isize, T, const R: isize, A, const W: usize> SpinRwLockEx<S, T, R, A, W> {
+    loom_const_fn! {
+        #[inline]
+        pub fn new(val: T) -> Self {
+            Self {
+                data: UnsafeCell::new(val),
+                readers: AtomicIsize::new(SPIN_RW_LOCK_UNLOCKED),
+                writers_waiting: AtomicUsize::new(0),
+                level: LockLevel::NONE,
+                stats: HoldStats::new(),
+                _align: [],
+            }
+        }
+    }
+    /// Initializes the lock directly in `slot`, without moving the whole lock (e.g. for a large `T` in
+    /// preallocated memory).
+    ///
+    /// # Safety
+    /// `slot` must be valid for writes and properly aligned for `Self`. Previous contents are overwritten
+    /// without being dropped.
     #[inline]
-    pub fn new(val: T) -> Self {
-        Self {
-            data: UnsafeCell::new(val),
-            readers: AtomicIsize::new(SPIN_RW_LOCK_UNLOCKED),
+    pub unsafe fn init_in_place(slot: *mut Self, val: T) {
+        // Safety: guaranteed by the caller. `_align` is zero-sized, so it needs no initialization.
+        unsafe {
+            UnsafeCell::raw_get(&raw const (*slot).data).write(val);
+            (&raw mut (*slot).readers).write(AtomicIsize::new(SPIN_RW_LOCK_UNLOCKED));
+            (&raw mut (*slot).writers_waiting).write(AtomicUsize::new(0));
+            (&raw mut (*slot).level).write(LockLevel::NONE);
+            (&raw mut (*slot).stats).write(HoldStats::new());
         }
     }
+    /// Assigns a level to this lock for the `lock-hierarchy` validation. No-op without the feature.
+    #[inline]
+    pub const fn with_level(mut self, level: usize) -> Self {
+        self.level = LockLevel::new(level);
+        self
+    }
+    /// Memory orderings used by this lock, see [`OrderingInfo`].
+    #[inline]
+    pub const fn ordering_info() -> OrderingInfo {
+        OrderingInfo::LOCK
+    }
     #[inline]
     pub fn into_inner(self) -> T {
         self.data.into_inner()
     }
+    /// `into_inner` for a lock shared through an `Arc`. Returns the `Arc` back if it is not the only one.
+    #[cfg(feature = "alloc")]
+    #[inline]
+    pub fn try_into_inner(self: alloc::sync::Arc<Self>) -> Result<T, alloc::sync::Arc<Self>> {
+        alloc::sync::Arc::try_unwrap(self).map(Self::into_inner)
+    }
     #[inline]
     pub fn get_mut(&mut self) -> &mut T {
         self.data.get_mut()
     }
+    /// Whether the lock is currently read or write locked. The result may already be outdated when it's
+    /// returned, so only use it for diagnostics.
     #[inline]
-    pub fn write(&self) -> SpinRwLockWriteGuardEx<'_, S, T> {
+    pub fn is_locked(&self) -> bool {
+        self.readers.load(Ordering::Relaxed) != SPIN_RW_LOCK_UNLOCKED
+    }
+    /// Number of readers currently holding the lock (0 if it is write locked). Same caveats as `is_locked`.
+    #[inline]
+    pub fn reader_count(&self) -> usize {
+        (self.readers.load(Ordering::Relaxed).max(0) & SPIN_RW_LOCK_READERS_MASK) as usize
+    }
+    /// Pretends `count` readers hold the lock, to test the reader cap without spawning that many guards.
+    #[cfg(test)]
+    fn set_reader_count(&self, count: isize) {
+        self.readers.store(count, Ordering::Relaxed);
+    }
+    /// Total time the write lock was held, in nanoseconds.
+    #[cfg(feature = "stats")]
+    #[inline]
+    pub fn total_write_hold_nanos(&self) -> u64 {
+        self.stats.total_nanos()
+    }
+    /// Number of write acquisitions that had to wait for the lock.
+    #[cfg(feature = "stats")]
+    #[inline]
+    pub fn contended_write_acquisitions(&self) -> usize {
+        self.stats.contended()
+    }
+    #[inline]
+    pub fn write(&self) -> SpinRwLockWriteGuardEx<'_, S, T, R, A, W> {
+        self.level.check();
         let mut backoff = Backoff::<S>::new();
+        let mut trace = ContentionTrace::new();
+        let mut rounds = 0;
+        let mut waiting = false;
         loop {
             if self.readers.load(Ordering::Relaxed) == SPIN_RW_LOCK_UNLOCKED
                 && self
@@ -92,36 +345,643 @@ impl<const S: isize, T> SpinRwLockEx<S, T> {
                     )
                     .is_ok()
             {
-                return SpinRwLockWriteGuardEx { lock: self };
+                if waiting {
+                    self.writers_waiting.fetch_sub(1, Ordering::Relaxed);
+                }
+                self.level.acquired();
+                trace.acquired::<Self>();
+                if rounds > 0 {
+                    self.stats.record_contended();
+                }
+                return SpinRwLockWriteGuardEx {
+                    lock: self,
+                    timer: self.stats.start(),
+                };
+            }
+            if W != usize::MAX && !waiting && rounds >= W {
+                self.writers_waiting.fetch_add(1, Ordering::Relaxed);
+                waiting = true;
             }
+            trace.snoozed::<Self>();
             backoff.snooze();
+            rounds += 1;
+        }
+    }
+    /// Number of writers that waited past the `W` threshold, and block new readers.
+    #[inline]
+    fn writers_waiting(&self) -> usize {
+        if W == usize::MAX {
+            0
+        } else {
+            self.writers_waiting.load(Ordering::Relaxed)
+        }
+    }
+    #[inline]
+    pub fn read(&self) -> SpinRwLockReadGuardEx<'_, S, T, R, A, W> {
+        const { assert!(R > 0, "Max readers must be positive") };
+        self.level.check();
+        if self.writers_waiting() != 0 {
+            return self.read_contended(self.readers.load(Ordering::Relaxed));
+        }
+        // Fast path for the uncontended case: unlocked -> single reader
+        match self.readers.compare_exchange(
+            SPIN_RW_LOCK_UNLOCKED,
+            SPIN_RW_LOCK_UNLOCKED + 1,
+            Ordering::Acquire,
+            Ordering::Relaxed,
+        ) {
+            Ok(_) => {
+                self.level.acquired();
+                SpinRwLockReadGuardEx { lock: self }
+            }
+            Err(current) => self.read_contended(current),
+        }
+    }
+    /// Single attempt to read lock, without spinning. Fails if the lock is write locked, has the max number of
+    /// readers, or a writer is waiting past the `W` threshold.
+    #[inline]
+    pub fn try_read(&self) -> Option<SpinRwLockReadGuardEx<'_, S, T, R, A, W>> {
+        const { assert!(R > 0, "Max readers must be positive") };
+        self.level.check();
+        let current = self.readers.load(Ordering::Relaxed);
+        if !self.can_read(current)
+            || self
+                .readers
+                .compare_exchange(current, current + 1, Ordering::Acquire, Ordering::Relaxed)
+                .is_err()
+        {
+            return None;
         }
+        self.level.acquired();
+        Some(SpinRwLockReadGuardEx { lock: self })
     }
-    pub fn read(&self) -> SpinRwLockReadGuardEx<'_, S, T> {
+    /// Single attempt to write lock, without spinning.
+    #[inline]
+    pub fn try_write(&self) -> Option<SpinRwLockWriteGuardEx<'_, S, T, R, A, W>> {
+        self.level.check();
+        if self
+            .readers
+            .compare_exchange(
+                SPIN_RW_LOCK_UNLOCKED,
+                SPIN_RW_LOCK_LOCKED,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            )
+            .is_err()
+        {
+            return None;
+        }
+        self.level.acquired();
+        Some(SpinRwLockWriteGuardEx {
+            lock: self,
+            timer: self.stats.start(),
+        })
+    }
+    /// Write locks, applies `f`, and downgrades to a read guard, so no other writer can get in before the
+    /// update is read back.
+    ///
+    /// To decide on the update while already reading, take an `upgradeable_read` guard instead, and call
+    /// [`update_and_keep_reading`](SpinRwLockUpgradeableGuardEx::update_and_keep_reading) on it, which
+    /// upgrades in place. Plain read guards can't be upgraded, so calling this while holding one deadlocks.
+    #[inline]
+    pub fn update_and_keep_reading(
+        &self,
+        f: impl FnOnce(&mut T),
+    ) -> SpinRwLockReadGuardEx<'_, S, T, R, A, W> {
+        let mut guard = self.write();
+        f(&mut guard);
+        SpinRwLockWriteGuardEx::downgrade(guard)
+    }
+    /// `R`, but never more than fit in the reader bits, so the count can't overflow into the state bits.
+    const MAX_READERS: isize = if R < SPIN_RW_LOCK_READERS_MASK {
+        R
+    } else {
+        SPIN_RW_LOCK_READERS_MASK
+    };
+    /// Whether a new reader can get in, when the state is `current`.
+    #[inline]
+    fn can_read(&self, current: isize) -> bool {
+        current != SPIN_RW_LOCK_LOCKED
+            && current & SPIN_RW_LOCK_UPGRADING == 0
+            && current & SPIN_RW_LOCK_READERS_MASK < Self::MAX_READERS
+            && self.writers_waiting() == 0
+    }
+    /// Read locks, and allows upgrading to a write lock later with
+    /// [`upgrade`](SpinRwLockUpgradeableGuardEx::upgrade). Plain readers can coexist with it, but there can
+    /// only be one upgradeable guard at a time, so others wait for it to be dropped (or upgraded and dropped).
+    ///
+    /// The upgradeable guard doesn't count against the `R` cap.
+    #[inline]
+    pub fn upgradeable_read(&self) -> SpinRwLockUpgradeableGuardEx<'_, S, T, R, A, W> {
+        self.level.check();
         let mut backoff = Backoff::<S>::new();
+        let mut trace = ContentionTrace::new();
         let mut current = self.readers.load(Ordering::Relaxed);
         loop {
-            if current == SPIN_RW_LOCK_LOCKED {
+            if current == SPIN_RW_LOCK_LOCKED
+                || current & SPIN_RW_LOCK_UPGRADEABLE != 0
+                || self.writers_waiting() != 0
+            {
+                trace.snoozed::<Self>();
                 backoff.snooze();
                 current = self.readers.load(Ordering::Relaxed);
                 continue;
             }
             match self.readers.compare_exchange(
                 current,
-                current.wrapping_add(1),
+                current | SPIN_RW_LOCK_UPGRADEABLE,
                 Ordering::Acquire,
                 Ordering::Relaxed,
             ) {
                 Ok(_) => {
+                    self.level.acquired();
+                    trace.acquired::<Self>();
+                    return SpinRwLockUpgradeableGuardEx { lock: self };
+                }
+                Err(prev) => current = prev,
+            }
+        }
+    }
+    #[cold]
+    fn read_contended(&self, mut current: isize) -> SpinRwLockReadGuardEx<'_, S, T, R, A, W> {
+        let mut backoff = Backoff::<S>::new();
+        let mut trace = ContentionTrace::contended::<Self>();
+        loop {
+            if !self.can_read(current) {
+                trace.snoozed::<Self>();
+                backoff.snooze();
+                current = self.readers.load(Ordering::Relaxed);
+                continue;
+            }
+            match self.readers.compare_exchange(
+                current,
+                current + 1,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => {
+                    self.level.acquired();
+                    trace.acquired::<Self>();
                     return SpinRwLockReadGuardEx { lock: self };
                 }
                 Err(prev) => {
                     current = prev;
+                    trace.snoozed::<Self>();
                     backoff.snooze();
                 }
             }
         }
     }
 }
-unsafe impl<const S: isize, T: Send> Send for SpinRwLockEx<S, T> {}
-unsafe impl<const S: isize, T: Send + Sync> Sync for SpinRwLockEx<S, T> {}
+impl<const S: isize, T, const R: isize, A, const W: usize> SpinRwLockEx<S, T, R, A, W> {
+    /// Runs `callback` under the read lock. The lock is released when it returns (or panics).
+    #[inline]
+    pub fn with_read<Ret>(&self, callback: impl FnOnce(&T) -> Ret) -> Ret {
+        let read_guard = self.read();
+        callback(&read_guard)
+    }
+    /// Runs `callback` under the write lock. The lock is released when it returns (or panics).
+    #[inline]
+    pub fn with_write<Ret>(&self, callback: impl FnOnce(&mut T) -> Ret) -> Ret {
+        let mut write_guard = self.write();
+        callback(&mut write_guard)
+    }
+    #[inline]
+    pub fn swap(&self, other: &mut T) {
+        mem::swap(&mut *self.write(), other)
+    }
+    #[inline]
+    pub fn replace(&self, other: T) -> T {
+        mem::replace(&mut *self.write(), other)
+    }
+}
+impl<const S: isize, T: Default, const R: isize, A, const W: usize> SpinRwLockEx<S, T, R, A, W> {
+    #[inline]
+    pub fn take(&self) -> T {
+        mem::take(&mut *self.write())
+    }
+}
+impl<const S: isize, T: PartialEq + Clone, const R: isize, A, const W: usize>
+    SpinRwLockEx<S, T, R, A, W>
+{
+    /// Replaces the value with `new` if it is equal to `current`, under the write lock.
+    ///
+    /// Returns the previous value, wrapped in `Ok` if it was replaced, and in `Err` otherwise.
+    #[inline]
+    pub fn compare_exchange(&self, current: T, new: T) -> Result<T, T> {
+        let mut guard = self.write();
+        if *guard == current {
+            Ok(mem::replace(&mut *guard, new))
+        } else {
+            Err(guard.clone())
+        }
+    }
+}
+unsafe impl<const S: isize, T: Send, const R: isize, A, const W: usize> Send
+    for SpinRwLockEx<S, T, R, A, W>
+{
+}
+unsafe impl<const S: isize, T: Send + Sync, const R: isize, A, const W: usize> Sync
+    for SpinRwLockEx<S, T, R, A, W>
+{
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn readers_wait_at_cap() {
+        let lock = SpinRwLockEx::<DEFAULT_SPIN_LIMIT, u32, 3>::new(0);
+        // Two readers already in
+        lock.set_reader_count(2);
+        let guard = lock.try_read().unwrap();
+        assert_eq!(lock.reader_count(), 3);
+        assert!(lock.try_read().is_none());
+        std::thread::scope(|s| {
+            let reader = s.spawn(|| *lock.read());
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            assert!(!reader.is_finished());
+            drop(guard);
+            assert_eq!(reader.join().unwrap(), 0);
+        });
+        lock.set_reader_count(0);
+        assert!(lock.try_write().is_some());
+    }
+
+    #[test]
+    fn read_fast_path_uncontended() {
+        let lock = SpinRwLock::new(5);
+        {
+            let a = lock.read();
+            let b = lock.read();
+            assert_eq!(lock.reader_count(), 2);
+            assert_eq!(*a + *b, 10);
+        }
+        assert!(!lock.is_locked());
+    }
+
+    #[test]
+    fn readers_and_writers_stress() {
+        let lock = SpinRwLock::new((0usize, 0usize));
+        std::thread::scope(|s| {
+            for _ in 0..2 {
+                s.spawn(|| {
+                    for _ in 0..2000 {
+                        let mut guard = lock.write();
+                        guard.0 += 1;
+                        guard.1 += 1;
+                    }
+                });
+            }
+            for _ in 0..2 {
+                s.spawn(|| {
+                    for _ in 0..2000 {
+                        let guard = lock.read();
+                        assert_eq!(guard.0, guard.1);
+                    }
+                });
+            }
+        });
+        assert_eq!(lock.into_inner(), (4000, 4000));
+    }
+
+    #[test]
+    fn compare_exchange_match_mismatch() {
+        let lock = SpinRwLock::new(1);
+        assert_eq!(lock.compare_exchange(1, 2), Ok(1));
+        assert_eq!(*lock.read(), 2);
+        assert_eq!(lock.compare_exchange(1, 3), Err(2));
+        assert_eq!(*lock.read(), 2);
+    }
+
+    #[test]
+    fn replace_swap_take() {
+        let lock = SpinRwLock::new(1);
+        assert_eq!(lock.replace(2), 1);
+        assert_eq!(*lock.read(), 2);
+        let mut other = 3;
+        lock.swap(&mut other);
+        assert_eq!(other, 2);
+        assert_eq!(*lock.read(), 3);
+        assert_eq!(lock.take(), 3);
+        assert_eq!(*lock.read(), 0);
+    }
+
+    /// Holds a read guard while a writer waits, and returns whether a new reader is blocked once the writer got
+    /// past the `W` threshold, or after `wait`.
+    fn readers_blocked_by_waiting_writer<const W: usize>(wait: std::time::Duration) -> bool {
+        use std::time::Instant;
+
+        let lock = SpinRwLockEx::<DEFAULT_SPIN_LIMIT, u32, { isize::MAX }, (), W>::new(0);
+        let guard = lock.read();
+        std::thread::scope(|s| {
+            s.spawn(|| *lock.write() += 1);
+            let deadline = Instant::now() + wait;
+            while lock.writers_waiting() == 0 && Instant::now() < deadline {
+                std::thread::yield_now();
+            }
+            let blocked = lock.try_read().is_none();
+            drop(guard);
+            blocked
+        })
+    }
+
+    #[test]
+    fn writer_threshold_crossover() {
+        use std::time::Duration;
+
+        // Below the threshold (default never reaches it), new readers still get in next to the waiting writer.
+        assert!(!readers_blocked_by_waiting_writer::<{ usize::MAX }>(
+            Duration::from_millis(50)
+        ));
+        assert!(!readers_blocked_by_waiting_writer::<{ usize::MAX - 1 }>(
+            Duration::from_millis(50)
+        ));
+        // Past the threshold, they wait for the writer.
+        assert!(readers_blocked_by_waiting_writer::<3>(Duration::from_secs(
+            10
+        )));
+        assert!(readers_blocked_by_waiting_writer::<0>(Duration::from_secs(
+            10
+        )));
+    }
+
+    #[test]
+    fn writers_waiting_cleared_after_acquire() {
+        let lock = WriterPreferringSpinRwLock::new(0u32);
+        std::thread::scope(|s| {
+            let guard = lock.read();
+            s.spawn(|| *lock.write() += 1);
+            while lock.writers_waiting() == 0 {
+                std::thread::yield_now();
+            }
+            drop(guard);
+        });
+        assert_eq!(lock.writers_waiting(), 0);
+        assert_eq!(*lock.try_read().unwrap(), 1);
+    }
+
+    #[test]
+    fn is_locked_and_reader_count_transitions() {
+        let lock = SpinRwLock::new(0u32);
+        assert!(!lock.is_locked());
+        assert_eq!(lock.reader_count(), 0);
+        let first = lock.read();
+        let second = lock.read();
+        assert!(lock.is_locked());
+        assert_eq!(lock.reader_count(), 2);
+        drop(first);
+        assert_eq!(lock.reader_count(), 1);
+        drop(second);
+        assert!(!lock.is_locked());
+
+        let guard = lock.write();
+        assert!(lock.is_locked());
+        assert_eq!(lock.reader_count(), 0);
+        drop(guard);
+        assert!(!lock.is_locked());
+    }
+
+    #[test]
+    fn try_read_try_write() {
+        let lock = SpinRwLock::new(0u32);
+        let reader = lock.try_read().unwrap();
+        assert!(lock.try_write().is_none());
+        let other_reader = lock.try_read().unwrap();
+        assert_eq!(lock.reader_count(), 2);
+        drop((reader, other_reader));
+
+        let writer = lock.try_write().unwrap();
+        assert!(lock.try_read().is_none());
+        assert!(lock.try_write().is_none());
+        drop(writer);
+        assert!(lock.try_write().is_some());
+    }
+
+    #[test]
+    fn init_in_place_large() {
+        let mut slot = core::mem::MaybeUninit::<SpinRwLock<[u8; 4096]>>::uninit();
+        // Safety: `slot` is valid for writes and aligned
+        unsafe { SpinRwLock::init_in_place(slot.as_mut_ptr(), [7; 4096]) };
+        // Safety: fully initialized by `init_in_place`
+        let lock = unsafe { slot.assume_init_ref() };
+        lock.write()[4095] = 1;
+        let guard = lock.read();
+        assert_eq!(guard[0], 7);
+        assert_eq!(guard[4095], 1);
+    }
+
+    #[test]
+    fn writer_completes_under_reader_stream() {
+        use core::sync::atomic::AtomicBool;
+
+        const MAX_READS: usize = 1_000_000;
+        let lock = WriterPreferringSpinRwLock::new(0u32);
+        let written = AtomicBool::new(false);
+        std::thread::scope(|s| {
+            for _ in 0..3 {
+                s.spawn(|| {
+                    let mut reads = 0;
+                    while !written.load(Ordering::Acquire) {
+                        drop(lock.read());
+                        reads += 1;
+                        assert!(reads < MAX_READS, "writer starved");
+                    }
+                });
+            }
+            s.spawn(|| {
+                *lock.write() = 1;
+                written.store(true, Ordering::Release);
+            });
+        });
+        assert_eq!(*lock.read(), 1);
+    }
+
+    #[test]
+    fn with_read_with_write_release_on_panic() {
+        use std::panic::{AssertUnwindSafe, catch_unwind};
+
+        let lock = SpinRwLock::new(0u32);
+        assert_eq!(
+            lock.with_write(|v| {
+                *v += 1;
+                *v
+            }),
+            1
+        );
+        assert_eq!(lock.with_read(|v| *v), 1);
+        assert!(!lock.is_locked());
+
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            lock.with_write(|_| panic!("in with_write"))
+        }));
+        assert!(result.is_err());
+        assert!(!lock.is_locked());
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            lock.with_read(|_| panic!("in with_read"))
+        }));
+        assert!(result.is_err());
+        assert!(!lock.is_locked());
+        assert!(lock.try_write().is_some());
+    }
+
+    #[cfg(not(loom))]
+    #[test]
+    fn default_and_static() {
+        static LOCK: SpinRwLock<u32> = SpinRwLock::new(5);
+        assert_eq!(*SpinRwLock::<u32>::default().read(), 0);
+        #[derive(Default)]
+        struct Holder {
+            lock: SpinRwLock<u32>,
+        }
+        assert_eq!(*Holder::default().lock.read(), 0);
+        *LOCK.write() += 1;
+        assert_eq!(*LOCK.read(), 6);
+    }
+
+    #[test]
+    fn near_max_readers_do_not_corrupt_state() {
+        let lock = SpinRwLock::new(0);
+        lock.set_reader_count(SPIN_RW_LOCK_READERS_MASK - 1);
+        let guard = lock.read();
+        assert_eq!(lock.reader_count(), SPIN_RW_LOCK_READERS_MASK as usize);
+        // At the cap: no more readers, and the count didn't carry into the upgradeable bits
+        assert!(lock.try_read().is_none());
+        assert!(lock.try_write().is_none());
+        assert_eq!(
+            lock.readers.load(Ordering::Relaxed),
+            SPIN_RW_LOCK_READERS_MASK
+        );
+        // The upgradeable guard doesn't count against the cap, and only sets its own bit
+        let upgradeable = lock.upgradeable_read();
+        assert_eq!(lock.reader_count(), SPIN_RW_LOCK_READERS_MASK as usize);
+        drop(upgradeable);
+        assert_eq!(
+            lock.readers.load(Ordering::Relaxed),
+            SPIN_RW_LOCK_READERS_MASK
+        );
+        drop(guard);
+        assert_eq!(lock.reader_count(), SPIN_RW_LOCK_READERS_MASK as usize - 1);
+        lock.set_reader_count(0);
+        assert!(!lock.is_locked());
+        assert!(lock.try_write().is_some());
+    }
+
+    #[test]
+    fn stays_locked_after_leak() {
+        let lock = SpinRwLock::new(1);
+        let data = SpinRwLockWriteGuardEx::leak(lock.write());
+        *data = 2;
+        assert!(lock.is_locked());
+        assert_eq!(lock.reader_count(), 0);
+        assert!(lock.try_read().is_none());
+        assert!(lock.try_write().is_none());
+        assert_eq!(*data, 2);
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn try_into_inner_arc() {
+        use std::sync::Arc;
+
+        let lock = Arc::new(SpinRwLock::new(5u32));
+        let other = Arc::clone(&lock);
+        let Err(lock) = lock.try_into_inner() else {
+            panic!("shared Arc was unwrapped");
+        };
+        assert!(Arc::ptr_eq(&lock, &other));
+        drop(other);
+        assert_eq!(lock.try_into_inner().ok(), Some(5));
+    }
+
+    #[test]
+    fn downgraded_reader_shares_with_readers_and_excludes_writers() {
+        let lock = SpinRwLock::new(0);
+        let mut write = lock.write();
+        *write = 1;
+        let read = SpinRwLockWriteGuardEx::downgrade(write);
+        assert_eq!(lock.reader_count(), 1);
+        std::thread::scope(|s| {
+            assert_eq!(s.spawn(|| *lock.read()).join().unwrap(), 1);
+            let writer = s.spawn(|| *lock.write() = 2);
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            assert!(!writer.is_finished());
+            assert_eq!(*read, 1);
+            drop(read);
+            writer.join().unwrap();
+        });
+        assert_eq!(*lock.read(), 2);
+    }
+
+    #[test]
+    fn single_upgradeable_guard() {
+        let lock = SpinRwLock::new(0);
+        let first = lock.upgradeable_read();
+        // Plain readers still get in
+        let plain = lock.try_read().unwrap();
+        std::thread::scope(|s| {
+            let second = s.spawn(|| *lock.upgradeable_read());
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            assert!(!second.is_finished());
+            drop(first);
+            assert_eq!(second.join().unwrap(), 0);
+        });
+        drop(plain);
+        assert!(!lock.is_locked());
+    }
+
+    #[test]
+    fn pending_upgrade_waits_for_readers_and_blocks_new_ones() {
+        let lock = SpinRwLock::new(0);
+        let reader = lock.read();
+        std::thread::scope(|s| {
+            let upgrader = s.spawn(|| {
+                let mut write = lock.upgradeable_read().upgrade();
+                *write = 1;
+            });
+            while lock.readers.load(Ordering::Relaxed) & SPIN_RW_LOCK_UPGRADING == 0 {
+                std::thread::yield_now();
+            }
+            assert!(lock.try_read().is_none());
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            assert!(!upgrader.is_finished());
+            assert_eq!(*reader, 0);
+            drop(reader);
+            upgrader.join().unwrap();
+        });
+        assert_eq!(*lock.read(), 1);
+    }
+
+    /// Checks that `read` (the guard returned by `update_and_keep_reading`) shares the lock with readers, and
+    /// keeps writers out until it's dropped.
+    fn assert_keeps_reading(lock: &SpinRwLock<u32>, read: SpinRwLockReadGuard<'_, u32>) {
+        assert_eq!(lock.reader_count(), 1);
+        assert!(lock.try_read().is_some());
+        std::thread::scope(|s| {
+            let writer = s.spawn(|| *lock.write() = 100);
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            assert!(!writer.is_finished());
+            assert_eq!(*read, 10);
+            drop(read);
+            writer.join().unwrap();
+        });
+        assert_eq!(*lock.read(), 100);
+    }
+
+    #[test]
+    fn update_and_keep_reading_cold() {
+        let lock = SpinRwLock::new(0);
+        let read = lock.update_and_keep_reading(|v| *v = 10);
+        assert_keeps_reading(&lock, read);
+    }
+
+    #[test]
+    fn update_and_keep_reading_warm() {
+        let lock = SpinRwLock::new(1);
+        let upgradeable = lock.upgradeable_read();
+        assert_eq!(*upgradeable, 1);
+        let read = upgradeable.update_and_keep_reading(|v| *v *= 10);
+        assert_keeps_reading(&lock, read);
+    }
+}
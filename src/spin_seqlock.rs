@@ -4,35 +4,154 @@ use core::{
     mem::{self, MaybeUninit},
     ops::{Deref, DerefMut},
     ptr,
-    sync::atomic::{AtomicUsize, Ordering, fence},
+    sync::atomic::Ordering,
 };
 
-use crate::backoff::{Backoff, DEFAULT_SPIN_LIMIT};
+use crate::{
+    backoff::{Backoff, DEFAULT_SPIN_LIMIT},
+    contention_trace::ContentionTrace,
+    hold_stats::{HoldStats, HoldTimer},
+    lock_hierarchy::LockLevel,
+    ordering_info::OrderingInfo,
+    sync::{AtomicU16, AtomicU32, AtomicUsize, fence, loom_const_fn},
+};
+use sealed::AtomicVersion;
+
+mod sealed {
+    use core::sync::atomic::Ordering;
+
+    pub trait AtomicVersion<V> {
+        fn load(&self, order: Ordering) -> V;
+        fn store(&self, val: V, order: Ordering);
+        fn compare_exchange(
+            &self,
+            current: V,
+            new: V,
+            success: Ordering,
+            failure: Ordering,
+        ) -> Result<V, V>;
+    }
+}
+
+/// Integer type used as the version counter of `SpinSeqLockEx`. Implemented for `u16`, `u32`, `u64` (only on
+/// targets with 64-bit atomics) and `usize` (default).
+///
+/// Narrower versions save memory, but wrap around sooner. When the version wraps, it skips the `LOCKED`
+/// value, but an optimistic reader that got preempted for exactly `2^bits - 1` writes could miss them, so
+/// only use `u16` when writes are rare.
+pub trait SeqLockVersion: Copy + Eq + core::fmt::Debug {
+    #[doc(hidden)]
+    type Atomic: AtomicVersion<Self>;
+    #[doc(hidden)]
+    #[cfg(not(loom))]
+    const INIT_ATOMIC: Self::Atomic;
+    #[doc(hidden)]
+    #[cfg(loom)]
+    fn init_atomic() -> Self::Atomic;
+    const LOCKED: Self;
+    const INIT_UNLOCKED: Self;
+    /// Version published after a write, skipping `LOCKED` on wraparound.
+    fn next_version(self) -> Self;
+}
+macro_rules! impl_seqlock_version {
+    ($($int:ty, $atomic:ty);*;) => {
+        $(
+        impl AtomicVersion<$int> for $atomic {
+            #[inline]
+            fn load(&self, order: Ordering) -> $int {
+                self.load(order)
+            }
+            #[inline]
+            fn store(&self, val: $int, order: Ordering) {
+                self.store(val, order)
+            }
+            #[inline]
+            fn compare_exchange(
+                &self,
+                current: $int,
+                new: $int,
+                success: Ordering,
+                failure: Ordering,
+            ) -> Result<$int, $int> {
+                self.compare_exchange(current, new, success, failure)
+            }
+        }
+        impl SeqLockVersion for $int {
+            type Atomic = $atomic;
+            #[cfg(not(loom))]
+            #[allow(clippy::declare_interior_mutable_const)]
+            const INIT_ATOMIC: $atomic = <$atomic>::new(1);
+            #[cfg(loom)]
+            fn init_atomic() -> $atomic {
+                <$atomic>::new(1)
+            }
+            const LOCKED: $int = 0;
+            const INIT_UNLOCKED: $int = 1;
+            #[inline]
+            fn next_version(self) -> $int {
+                match self.wrapping_add(1) {
+                    Self::LOCKED => Self::INIT_UNLOCKED,
+                    next => next,
+                }
+            }
+        }
+        )*
+    };
+}
+impl_seqlock_version! {
+    u16, AtomicU16;
+    u32, AtomicU32;
+    usize, AtomicUsize;
+}
+#[cfg(any(target_has_atomic = "64", loom))]
+impl_seqlock_version! {
+    u64, crate::sync::AtomicU64;
+}
+
+#[cfg(not(loom))]
+#[inline(always)]
+const fn init_version<V: SeqLockVersion>() -> V::Atomic {
+    V::INIT_ATOMIC
+}
+#[cfg(loom)]
+#[inline(always)]
+fn init_version<V: SeqLockVersion>() -> V::Atomic {
+    V::init_atomic()
+}
 
 pub type SpinSeqLock<T> = SpinSeqLockEx<DEFAULT_SPIN_LIMIT, T>;
 pub type SpinSeqLockReadGuard<'a, T> = SpinSeqLockReadGuardEx<'a, DEFAULT_SPIN_LIMIT, T>;
 pub type SpinSeqLockWriteGuard<'a, T> = SpinSeqLockWriteGuardEx<'a, DEFAULT_SPIN_LIMIT, T>;
 
-pub struct SpinSeqLockEx<const B: isize, T> {
+/// `A` sets the alignment of the lock, see [`align`](crate::align).
+pub struct SpinSeqLockEx<const B: isize, T, V: SeqLockVersion = usize, A = ()> {
     data: UnsafeCell<T>,
-    version: AtomicUsize,
+    version: V::Atomic,
+    /// Last version published by an unlock, so `recover` can publish a newer one.
+    #[cfg(feature = "seqlock-recover")]
+    last_unlocked: V::Atomic,
+    level: LockLevel,
+    stats: HoldStats,
+    _align: [A; 0],
 }
 
-impl<const N: isize, T> SpinSeqLockEx<N, T> {
-    const INIT_UNLOCKED: usize = 1;
-    const LOCKED: usize = 0;
+impl<const B: isize, T, V: SeqLockVersion, A> SpinSeqLockEx<B, T, V, A> {
+    const LOCKED: V = V::LOCKED;
 }
-pub struct SpinSeqLockReadGuardEx<'a, const B: isize, T> {
-    cell: &'a SpinSeqLockEx<B, T>,
-    prev: usize,
+pub struct SpinSeqLockReadGuardEx<'a, const B: isize, T, V: SeqLockVersion = usize, A = ()> {
+    cell: &'a SpinSeqLockEx<B, T, V, A>,
+    prev: V,
 }
-impl<const B: isize, T> Drop for SpinSeqLockReadGuardEx<'_, B, T> {
+impl<const B: isize, T, V: SeqLockVersion, A> Drop for SpinSeqLockReadGuardEx<'_, B, T, V, A> {
     #[inline]
     fn drop(&mut self) {
+        #[cfg(feature = "seqlock-recover")]
+        self.cell.last_unlocked.store(self.prev, Ordering::Relaxed);
         self.cell.version.store(self.prev, Ordering::Release);
+        self.cell.level.released();
     }
 }
-impl<const B: isize, T> Deref for SpinSeqLockReadGuardEx<'_, B, T> {
+impl<const B: isize, T, V: SeqLockVersion, A> Deref for SpinSeqLockReadGuardEx<'_, B, T, V, A> {
     type Target = T;
     #[inline]
     fn deref(&self) -> &Self::Target {
@@ -40,20 +159,25 @@ impl<const B: isize, T> Deref for SpinSeqLockReadGuardEx<'_, B, T> {
         unsafe { &(*self.cell.data.get()) }
     }
 }
-impl<const B: isize, T> SpinSeqLockEx<B, T> {
+impl<const B: isize, T, V: SeqLockVersion, A> SpinSeqLockEx<B, T, V, A> {
     #[inline]
-    pub fn read(&self) -> SpinSeqLockReadGuardEx<'_, B, T> {
+    pub fn read(&self) -> SpinSeqLockReadGuardEx<'_, B, T, V, A> {
         let mut backoff = Backoff::<B>::new();
+        let mut trace = ContentionTrace::new();
         loop {
             let Some(guard) = self.try_read() else {
+                trace.snoozed::<Self>();
                 backoff.snooze();
                 continue;
             };
+            trace.acquired::<Self>();
             return guard;
         }
     }
+    /// Single attempt to read lock, without spinning.
     #[inline]
-    pub fn try_read(&self) -> Option<SpinSeqLockReadGuardEx<'_, B, T>> {
+    pub fn try_read(&self) -> Option<SpinSeqLockReadGuardEx<'_, B, T, V, A>> {
+        self.level.check();
         let prev = self.version.load(Ordering::Relaxed);
 
         if prev != Self::LOCKED
@@ -62,23 +186,29 @@ impl<const B: isize, T> SpinSeqLockEx<B, T> {
                 .compare_exchange(prev, Self::LOCKED, Ordering::Acquire, Ordering::Relaxed)
                 .is_ok()
         {
+            self.level.acquired();
             return Some(SpinSeqLockReadGuardEx { cell: self, prev });
         }
         None
     }
 }
 
-pub struct SpinSeqLockWriteGuardEx<'a, const B: isize, T> {
-    cell: &'a SpinSeqLockEx<B, T>,
-    next: usize,
+pub struct SpinSeqLockWriteGuardEx<'a, const B: isize, T, V: SeqLockVersion = usize, A = ()> {
+    cell: &'a SpinSeqLockEx<B, T, V, A>,
+    next: V,
+    timer: HoldTimer,
 }
-impl<const B: isize, T> Drop for SpinSeqLockWriteGuardEx<'_, B, T> {
+impl<const B: isize, T, V: SeqLockVersion, A> Drop for SpinSeqLockWriteGuardEx<'_, B, T, V, A> {
     #[inline]
     fn drop(&mut self) {
+        self.cell.stats.stop(&self.timer);
+        #[cfg(feature = "seqlock-recover")]
+        self.cell.last_unlocked.store(self.next, Ordering::Relaxed);
         self.cell.version.store(self.next, Ordering::Release);
+        self.cell.level.released();
     }
 }
-impl<const B: isize, T> Deref for SpinSeqLockWriteGuardEx<'_, B, T> {
+impl<const B: isize, T, V: SeqLockVersion, A> Deref for SpinSeqLockWriteGuardEx<'_, B, T, V, A> {
     type Target = T;
     #[inline]
     fn deref(&self) -> &Self::Target {
@@ -86,27 +216,89 @@ impl<const B: isize, T> Deref for SpinSeqLockWriteGuardEx<'_, B, T> {
         unsafe { &(*self.cell.data.get()) }
     }
 }
-impl<const B: isize, T> DerefMut for SpinSeqLockWriteGuardEx<'_, B, T> {
+impl<const B: isize, T, V: SeqLockVersion, A> DerefMut for SpinSeqLockWriteGuardEx<'_, B, T, V, A> {
     #[inline]
     fn deref_mut(&mut self) -> &mut Self::Target {
         // Safety: safe to deref while we hold the write lock
         unsafe { &mut (*self.cell.data.get()) }
     }
 }
-impl<const B: isize, T> SpinSeqLockEx<B, T> {
+/// Write guard that runs a hook on the final value right before it is published.
+pub struct SpinSeqLockHookedWriteGuardEx<
+    'a,
+    const B: isize,
+    T,
+    F: FnOnce(&T),
+    V: SeqLockVersion = usize,
+    A = (),
+> {
+    guard: SpinSeqLockWriteGuardEx<'a, B, T, V, A>,
+    on_commit: Option<F>,
+}
+impl<const B: isize, T, F: FnOnce(&T), V: SeqLockVersion, A> Drop
+    for SpinSeqLockHookedWriteGuardEx<'_, B, T, F, V, A>
+{
+    #[inline]
+    fn drop(&mut self) {
+        // `guard` field is dropped (and the lock released) after this, even if the hook panics
+        if let Some(on_commit) = self.on_commit.take() {
+            on_commit(&self.guard);
+        }
+    }
+}
+impl<const B: isize, T, F: FnOnce(&T), V: SeqLockVersion, A> Deref
+    for SpinSeqLockHookedWriteGuardEx<'_, B, T, F, V, A>
+{
+    type Target = T;
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.guard
+    }
+}
+impl<const B: isize, T, F: FnOnce(&T), V: SeqLockVersion, A> DerefMut
+    for SpinSeqLockHookedWriteGuardEx<'_, B, T, F, V, A>
+{
     #[inline]
-    pub fn write(&self) -> SpinSeqLockWriteGuardEx<'_, B, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.guard
+    }
+}
+impl<const B: isize, T, V: SeqLockVersion, A> SpinSeqLockEx<B, T, V, A> {
+    /// Same as `write`, but `on_commit` runs on the final value when the guard is dropped, while the lock is
+    /// still held and before the new version is published.
+    #[inline]
+    pub fn write_with_hook<F: FnOnce(&T)>(
+        &self,
+        on_commit: F,
+    ) -> SpinSeqLockHookedWriteGuardEx<'_, B, T, F, V, A> {
+        SpinSeqLockHookedWriteGuardEx {
+            guard: self.write(),
+            on_commit: Some(on_commit),
+        }
+    }
+    #[inline]
+    pub fn write(&self) -> SpinSeqLockWriteGuardEx<'_, B, T, V, A> {
         let mut backoff = Backoff::<B>::new();
+        let mut trace = ContentionTrace::new();
+        let mut contended = false;
         loop {
             let Some(guard) = self.try_write() else {
+                trace.snoozed::<Self>();
                 backoff.snooze();
+                contended = true;
                 continue;
             };
+            trace.acquired::<Self>();
+            if contended {
+                self.stats.record_contended();
+            }
             return guard;
         }
     }
+    /// Single attempt to write lock, without spinning.
     #[inline]
-    pub fn try_write(&self) -> Option<SpinSeqLockWriteGuardEx<'_, B, T>> {
+    pub fn try_write(&self) -> Option<SpinSeqLockWriteGuardEx<'_, B, T, V, A>> {
+        self.level.check();
         let prev = self.version.load(Ordering::Relaxed);
 
         if prev != Self::LOCKED
@@ -115,9 +307,11 @@ impl<const B: isize, T> SpinSeqLockEx<B, T> {
                 .compare_exchange(prev, Self::LOCKED, Ordering::Acquire, Ordering::Relaxed)
                 .is_ok()
         {
+            self.level.acquired();
             return Some(SpinSeqLockWriteGuardEx {
                 cell: self,
-                next: prev + 1,
+                next: prev.next_version(),
+                timer: self.stats.start(),
             });
         }
 
@@ -125,7 +319,7 @@ impl<const B: isize, T> SpinSeqLockEx<B, T> {
     }
 }
 
-impl<const B: isize, T> SpinSeqLockEx<B, T> {
+impl<const B: isize, T, V: SeqLockVersion, A> SpinSeqLockEx<B, T, V, A> {
     #[inline]
     pub fn access<R>(&self, callback: impl FnOnce(&T) -> R) -> R {
         let read_guard = self.read();
@@ -136,9 +330,61 @@ impl<const B: isize, T> SpinSeqLockEx<B, T> {
         let mut write_guard = self.write();
         callback(&mut write_guard)
     }
+    /// Runs `f` on the value under the lock if `pred` holds, and returns whether it did.
+    ///
+    /// Unlike conditionally writing inside `access_mut`, the version is bumped only if `f` runs, so optimistic
+    /// readers don't have to retry because of writes that didn't change anything.
+    #[inline]
+    pub fn update_if(&self, pred: impl FnOnce(&T) -> bool, f: impl FnOnce(&mut T)) -> bool {
+        // Read guard also locks exclusively, and only restores the version on drop.
+        let mut guard = self.read();
+        if !pred(&guard) {
+            return false;
+        }
+        // Bump before `f` runs, so readers don't trust a value that `f` panicked halfway through writing.
+        guard.prev = guard.prev.next_version();
+        // Safety: we hold the lock exclusively
+        f(unsafe { &mut *self.data.get() });
+        true
+    }
+    /// Runs `f` on the value under the read lock. Same as `access`, the blocking counterpart of
+    /// `read_scoped_optimistic`.
+    #[inline]
+    pub fn read_scoped<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        self.access(f)
+    }
+    /// Runs `f` on a pointer to the value without taking the lock (for any `T`, not just `Copy`), and re-runs
+    /// it if a write happened in the meantime. Falls back to running it under the lock after a few failed
+    /// attempts, and always under `miri`.
+    ///
+    /// Useful to read a few fields of a large `T`, without copying the whole value like `load` does.
+    ///
+    /// `f` gets a raw pointer, not a `&T`, because the value can be concurrently written to, and a reference
+    /// to it would be UB. `f` should only copy plain data out of it with `ptr::read_volatile` (see
+    /// `optimistic_read`), e.g. `|p| unsafe { ptr::read_volatile(&raw const (*p).field) }`. Anything it reads
+    /// may be torn, and its result is discarded in that case, so it must not follow pointers in `T`
+    /// (including `Box`, `Vec`, `String`, ...), index with values read from it, loop on it, construct types
+    /// with validity invariants (`bool`, enums, references) from it, panic on invalid values, or have any side
+    /// effects.
+    #[inline]
+    pub fn read_scoped_optimistic<R>(&self, f: impl Fn(*const T) -> R) -> R {
+        #[cfg(not(miri))]
+        for _ in 0..DEFAULT_SPIN_LIMIT {
+            let version = self.version.load(Ordering::Acquire);
+            if version != Self::LOCKED {
+                let result = f(self.data.get().cast_const());
+                fence(Ordering::Acquire);
+                if self.version.load(Ordering::Relaxed) == version {
+                    return result;
+                }
+                record_torn_read();
+            }
+        }
+        self.access(|data| f(ptr::from_ref(data)))
+    }
 }
 
-impl<const B: isize, T: Copy> SpinSeqLockEx<B, T> {
+impl<const B: isize, T: Copy, V: SeqLockVersion, A> SpinSeqLockEx<B, T, V, A> {
     #[inline]
     fn optimistic_read(&self) -> Option<T> {
         #[cfg(not(miri))]
@@ -149,12 +395,18 @@ impl<const B: isize, T: Copy> SpinSeqLockEx<B, T> {
                 // In Rust/C++ memory model, data races are *always UB*, even if we can always
                 // detect the data race and discard the result.
                 // LLVM memory model allows for this use case, which is probably the reason things dont blow up.
-                let data = unsafe { ptr::read_volatile(self.data.get().cast::<MaybeUninit<T>>()) };
+                // Zero-sized T (e.g. a `SpinSeqLock<()>` token) has no bytes to race on, so skip the read.
+                let data = if mem::size_of::<T>() == 0 {
+                    MaybeUninit::uninit()
+                } else {
+                    unsafe { ptr::read_volatile(self.data.get().cast::<MaybeUninit<T>>()) }
+                };
                 fence(Ordering::Acquire);
                 if self.version.load(Ordering::Relaxed) == version {
                     // Safety: since the version did not change, we can be sure that there was no writes while we were reading the value.
                     return Some(unsafe { data.assume_init() });
                 }
+                record_torn_read();
             }
         }
         None
@@ -163,14 +415,300 @@ impl<const B: isize, T: Copy> SpinSeqLockEx<B, T> {
     pub fn load(&self) -> T {
         self.optimistic_read().unwrap_or_else(|| *self.read())
     }
+    /// Alias of [`replace`](Self::replace), under the name of the atomic `swap` it's the seqlock analog of.
+    ///
+    /// It is not lock-free, since it takes the write lock. The new value is published with a single version bump,
+    /// so optimistic readers see either the old or the new value, never a mix of both.
+    #[inline]
+    pub fn swap_atomic(&self, new: T) -> T {
+        self.replace(new)
+    }
+    /// Retries optimistic reads until one succeeds, or `timeout` passes. Never takes the lock.
+    ///
+    /// Clock is checked once per batch of optimistic attempts. Always returns `None` under `miri`.
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn load_timeout(&self, timeout: std::time::Duration) -> Option<T> {
+        #[cfg(miri)]
+        {
+            let _ = timeout;
+            None
+        }
+        #[cfg(not(miri))]
+        {
+            const BATCH: usize = 16;
+            let start = std::time::Instant::now();
+            let mut backoff = Backoff::<B>::new();
+            loop {
+                for _ in 0..BATCH {
+                    if let Some(data) = self.optimistic_read() {
+                        return Some(data);
+                    }
+                    backoff.snooze();
+                }
+                if start.elapsed() >= timeout {
+                    return None;
+                }
+            }
+        }
+    }
+    /// Optimistically reads the value directly into `dst`, without going through a return-by-value copy.
+    ///
+    /// Returns `true` if the read was validated, in which case `dst` was initialized with the value.
+    /// When it returns `false` (all optimistic attempts ran into concurrent writes), `dst` might contain a
+    /// torn mix of bytes, and must not be assumed initialized.
+    ///
+    /// Never takes the lock (and always returns `false` under `miri`).
+    #[inline]
+    pub fn read_into(&self, dst: &mut MaybeUninit<T>) -> bool {
+        #[cfg(not(miri))]
+        for _ in 0..DEFAULT_SPIN_LIMIT {
+            let version = self.version.load(Ordering::Acquire);
+            if version != Self::LOCKED {
+                // Safety: see `optimistic_read`
+                unsafe { volatile_copy(self.data.get(), dst.as_mut_ptr()) };
+                fence(Ordering::Acquire);
+                if self.version.load(Ordering::Relaxed) == version {
+                    return true;
+                }
+                record_torn_read();
+            }
+        }
+        #[cfg(miri)]
+        let _ = dst;
+        false
+    }
+    /// First half of an optimistic read: returns the version, and a copy of the value that may be torn by a
+    /// concurrent write. Never takes the lock.
+    ///
+    /// Only for `T: Copy`: for other types, the bitwise copy would be a second owner of the value (and drop it
+    /// twice).
+    ///
+    /// The copy may only be used (`assume_init`) after [`validate_read`](Self::validate_read) returned `true`
+    /// for the returned version. Validating after several `read_version` calls (on the same or different
+    /// locks) checks all of them at once, which gives a consistent snapshot across multiple locks.
+    ///
+    /// Under `miri`, the copy is never read, and the returned version is always `V::LOCKED`, so validation
+    /// always fails. Validation can also keep failing under heavy write contention, so callers need a locked
+    /// fallback, e.g. taking the read locks (in a fixed order across all threads, to avoid deadlocks):
+    ///
+    /// ```
+    /// use atomics::spin_seqlock::SpinSeqLock;
+    ///
+    /// /// Reads both fields as they were at a single point in time.
+    /// fn snapshot(a: &SpinSeqLock<u64>, b: &SpinSeqLock<u64>) -> (u64, u64) {
+    ///     for _ in 0..4 {
+    ///         let (version_a, value_a) = a.read_version();
+    ///         let (version_b, value_b) = b.read_version();
+    ///         if a.validate_read(version_a) && b.validate_read(version_b) {
+    ///             // Safety: both reads were validated
+    ///             return unsafe { (value_a.assume_init(), value_b.assume_init()) };
+    ///         }
+    ///     }
+    ///     let (guard_a, guard_b) = (a.read(), b.read());
+    ///     (*guard_a, *guard_b)
+    /// }
+    ///
+    /// let (balance, reserved) = (SpinSeqLock::new(100), SpinSeqLock::new(0));
+    /// assert_eq!(snapshot(&balance, &reserved), (100, 0));
+    /// {
+    ///     // Move 30 from `balance` to `reserved`. Readers see both fields before or after the move.
+    ///     let (mut balance, mut reserved) = (balance.write(), reserved.write());
+    ///     *balance -= 30;
+    ///     *reserved += 30;
+    /// }
+    /// assert_eq!(snapshot(&balance, &reserved), (70, 30));
+    /// ```
+    #[inline]
+    pub fn read_version(&self) -> (V, MaybeUninit<T>) {
+        #[cfg(not(miri))]
+        {
+            let version = self.version.load(Ordering::Acquire);
+            let data = if version == Self::LOCKED || mem::size_of::<T>() == 0 {
+                MaybeUninit::uninit()
+            } else {
+                // Safety: see `optimistic_read`. The copy is only used if the version did not change.
+                unsafe { ptr::read_volatile(self.data.get().cast::<MaybeUninit<T>>()) }
+            };
+            fence(Ordering::Acquire);
+            (version, data)
+        }
+        #[cfg(miri)]
+        (Self::LOCKED, MaybeUninit::uninit())
+    }
+    /// Optimistically reads just the `R` that `project` points to (e.g. a field of a large `T`), without
+    /// copying the whole value like `load` does. Falls back to reading it under the lock after a few failed
+    /// attempts, and always under `miri`, same as `read_scoped_optimistic`.
+    ///
+    /// # Safety
+    /// `project` must return a pointer to an `R` within the value its argument points to, without reading
+    /// through it, e.g. `|p| unsafe { &raw const (*p).field }`.
+    #[inline]
+    pub unsafe fn load_projection<R: Copy>(&self, project: impl Fn(*const T) -> *const R) -> R {
+        let data = self.read_scoped_optimistic(|p| {
+            // Safety: guaranteed by the caller. The copy may be torn, so it's only assumed initialized after
+            // `read_scoped_optimistic` validated it.
+            unsafe { ptr::read_volatile(project(p).cast::<MaybeUninit<R>>()) }
+        });
+        // Safety: validated (or read under the lock)
+        unsafe { data.assume_init() }
+    }
 }
 
-unsafe impl<const B: isize, T: Send> Send for SpinSeqLockEx<B, T> {}
+impl<const B: isize, T: Ord + Copy, V: SeqLockVersion, A> SpinSeqLockEx<B, T, V, A> {
+    /// Stores the minimum of the current value and `v`, and returns the previous value.
+    ///
+    /// Does not bump the version (or even take the lock, if the optimistic read succeeds) when the current
+    /// value is already `<= v`.
+    #[inline]
+    pub fn fetch_min(&self, v: T) -> T {
+        self.fetch_replace_if(v, |current| v < current)
+    }
+    /// Stores the maximum of the current value and `v`, and returns the previous value.
+    ///
+    /// Does not bump the version (or even take the lock, if the optimistic read succeeds) when the current
+    /// value is already `>= v`.
+    #[inline]
+    pub fn fetch_max(&self, v: T) -> T {
+        self.fetch_replace_if(v, |current| v > current)
+    }
+    #[inline]
+    fn fetch_replace_if(&self, v: T, replace: impl Fn(T) -> bool) -> T {
+        let current = self.load();
+        if !replace(current) {
+            return current;
+        }
+        // Read guard also locks exclusively, and only restores the version on drop, so we bump it only if
+        // we actually write.
+        let mut guard = self.read();
+        let current = *guard;
+        if replace(current) {
+            // Safety: we hold the lock exclusively
+            unsafe { *self.data.get() = v };
+            guard.prev = guard.prev.next_version();
+        }
+        current
+    }
+}
+impl<const B: isize, T: PartialEq + Copy, V: SeqLockVersion, A> SpinSeqLockEx<B, T, V, A> {
+    /// Stores `new` if the current value equals `current`. Returns the previous value, in `Ok` if it was
+    /// replaced, and in `Err` otherwise.
+    ///
+    /// Same idiom as `AtomicT*::compare_exchange`, but it takes the lock, so it's not lock-free. The version is
+    /// only bumped if `new` is stored.
+    #[inline]
+    pub fn compare_exchange(&self, current: T, new: T) -> Result<T, T> {
+        // Read guard also locks exclusively, same as in `fetch_replace_if`.
+        let mut guard = self.read();
+        let prev = *guard;
+        if prev != current {
+            return Err(prev);
+        }
+        // Safety: we hold the lock exclusively
+        unsafe { *self.data.get() = new };
+        guard.prev = guard.prev.next_version();
+        Ok(prev)
+    }
+}
+
+/// Optimistically reads both `a` and `b`, and returns the pair only if neither of them was written to while
+/// they were read.
+///
+/// Makes a single attempt and never takes the locks, so it returns `None` on any conflict (and always under
+/// `miri`).
+#[inline]
+pub fn read_coherent<
+    const B1: isize,
+    T1: Copy,
+    V1: SeqLockVersion,
+    A1,
+    const B2: isize,
+    T2: Copy,
+    V2: SeqLockVersion,
+    A2,
+>(
+    a: &SpinSeqLockEx<B1, T1, V1, A1>,
+    b: &SpinSeqLockEx<B2, T2, V2, A2>,
+) -> Option<(T1, T2)> {
+    #[cfg(not(miri))]
+    {
+        let version_a = a.version.load(Ordering::Acquire);
+        let version_b = b.version.load(Ordering::Acquire);
+        if version_a == V1::LOCKED || version_b == V2::LOCKED {
+            return None;
+        }
+        // Safety: see `optimistic_read`
+        let data_a = unsafe { ptr::read_volatile(a.data.get().cast::<MaybeUninit<T1>>()) };
+        let data_b = unsafe { ptr::read_volatile(b.data.get().cast::<MaybeUninit<T2>>()) };
+        fence(Ordering::Acquire);
+        if a.version.load(Ordering::Relaxed) == version_a
+            && b.version.load(Ordering::Relaxed) == version_b
+        {
+            // Safety: no writes happened to either of the locks while we were reading them.
+            return Some(unsafe { (data_a.assume_init(), data_b.assume_init()) });
+        }
+        record_torn_read();
+    }
+    #[cfg(miri)]
+    let _ = (a, b);
+    None
+}
+
+#[cfg(feature = "seqlock-debug")]
+static TORN_READS: core::sync::atomic::AtomicUsize = core::sync::atomic::AtomicUsize::new(0);
+
+/// Number of optimistic reads (of all seqlocks) that were discarded because a write happened while reading.
+#[cfg(feature = "seqlock-debug")]
+#[inline]
+pub fn torn_read_count() -> usize {
+    TORN_READS.load(Ordering::Relaxed)
+}
+#[inline]
+pub(crate) fn record_torn_read() {
+    #[cfg(feature = "seqlock-debug")]
+    TORN_READS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Loads every lock in `locks`.
+///
+/// Each lock is loaded separately, so this is not a consistent point-in-time snapshot of the whole slice.
+#[cfg(feature = "alloc")]
+pub fn snapshot_all<const B: isize, T: Copy, V: SeqLockVersion, A>(
+    locks: &[SpinSeqLockEx<B, T, V, A>],
+) -> alloc::vec::Vec<T> {
+    locks.iter().map(SpinSeqLockEx::load).collect()
+}
+
+/// Copies `T` from `src` to `dst` with volatile reads, word by word when `T` is aligned enough.
+///
+/// # Safety
+/// Same as `ptr::read_volatile` for `src` and `ptr::write` for `dst`.
+#[cfg(not(miri))]
+#[inline]
+unsafe fn volatile_copy<T>(src: *const T, dst: *mut T) {
+    if mem::align_of::<T>() >= mem::align_of::<usize>() {
+        // size of a `T` aligned to usize is a multiple of usize
+        let words = mem::size_of::<T>() / mem::size_of::<usize>();
+        let src = src.cast::<MaybeUninit<usize>>();
+        let dst = dst.cast::<MaybeUninit<usize>>();
+        for i in 0..words {
+            unsafe { dst.add(i).write(ptr::read_volatile(src.add(i))) };
+        }
+    } else {
+        let src = src.cast::<MaybeUninit<u8>>();
+        let dst = dst.cast::<MaybeUninit<u8>>();
+        for i in 0..mem::size_of::<T>() {
+            unsafe { dst.add(i).write(ptr::read_volatile(src.add(i))) };
+        }
+    }
+}
+
+unsafe impl<const B: isize, T: Send, V: SeqLockVersion, A> Send for SpinSeqLockEx<B, T, V, A> {}
 /// SAFETY: SpinSeqLock only supports copying out values, or single &T referece (via read-locking), so no need for
 /// `Sync` bound on `T`.
-unsafe impl<const B: isize, T: Send> Sync for SpinSeqLockEx<B, T> {}
+unsafe impl<const B: isize, T: Send, V: SeqLockVersion, A> Sync for SpinSeqLockEx<B, T, V, A> {}
 
-impl<const B: isize, T> SpinSeqLockEx<B, T> {
+impl<const B: isize, T, V: SeqLockVersion, A> SpinSeqLockEx<B, T, V, A> {
     #[inline]
     pub fn swap(&self, other: &mut T) {
         mem::swap(&mut *self.write(), other)
@@ -179,87 +717,236 @@ impl<const B: isize, T> SpinSeqLockEx<B, T> {
     pub fn replace_mut(&mut self, other: T) -> T {
         mem::replace(self.get_mut(), other)
     }
+    /// Stores `other` under the write lock, and returns the previous value.
     #[inline]
     pub fn replace(&self, other: T) -> T {
         mem::replace(&mut *self.write(), other)
     }
+    /// Same as `replace`, but also returns the version published by this write.
+    ///
+    /// Every write increases the version (until it wraps around), so it can be used as an epoch stamp, e.g. to
+    /// key deferred cleanup of the returned value.
     #[inline]
-    pub const fn new(val: T) -> Self {
-        Self {
-            data: UnsafeCell::new(val),
-            version: AtomicUsize::new(Self::INIT_UNLOCKED),
+    pub fn replace_versioned(&self, other: T) -> (T, V) {
+        let mut write_guard = self.write();
+        let version = write_guard.next;
+        (mem::replace(&mut *write_guard, other), version)
+    }
+    loom_const_fn! {
+        #[inline]
+        pub fn new(val: T) -> Self {
+            Self {
+                data: UnsafeCell::new(val),
+                version: init_version::<V>(),
+                #[cfg(feature = "seqlock-recover")]
+                last_unlocked: init_version::<V>(),
+                level: LockLevel::NONE,
+                stats: HoldStats::new(),
+                _align: [],
+            }
+        }
+    }
+    /// Initializes the lock directly in `slot`, without moving the whole lock (e.g. for a large `T` in
+    /// preallocated memory).
+    ///
+    /// # Safety
+    /// `slot` must be valid for writes and properly aligned for `Self`. Previous contents are overwritten
+    /// without being dropped.
+    #[inline]
+    pub unsafe fn init_in_place(slot: *mut Self, val: T) {
+        // Safety: guaranteed by the caller. `_align` is zero-sized, so it needs no initialization.
+        unsafe {
+            UnsafeCell::raw_get(&raw const (*slot).data).write(val);
+            (&raw mut (*slot).version).write(init_version::<V>());
+            #[cfg(feature = "seqlock-recover")]
+            (&raw mut (*slot).last_unlocked).write(init_version::<V>());
+            (&raw mut (*slot).level).write(LockLevel::NONE);
+            (&raw mut (*slot).stats).write(HoldStats::new());
         }
     }
+    /// Assigns a level to this lock for the `lock-hierarchy` validation. No-op without the feature.
+    #[inline]
+    pub const fn with_level(mut self, level: usize) -> Self {
+        self.level = LockLevel::new(level);
+        self
+    }
+    /// Memory orderings used by this lock, see [`OrderingInfo`].
+    #[inline]
+    pub const fn ordering_info() -> OrderingInfo {
+        OrderingInfo::SEQLOCK
+    }
     #[inline]
     pub fn into_inner(self) -> T {
         self.data.into_inner()
     }
+    /// `into_inner` for a lock shared through an `Arc`. Returns the `Arc` back if it is not the only one.
+    #[cfg(feature = "alloc")]
+    #[inline]
+    pub fn try_into_inner(self: alloc::sync::Arc<Self>) -> Result<T, alloc::sync::Arc<Self>> {
+        alloc::sync::Arc::try_unwrap(self).map(Self::into_inner)
+    }
     #[inline]
     pub fn get_mut(&mut self) -> &mut T {
         self.data.get_mut()
     }
+    /// Returns a raw pointer to the guarded value, e.g. to optimistically read a single field.
+    ///
+    /// Like `AtomicT::as_ptr`, getting the pointer is safe, but using it is not: all the seqlock invariants
+    /// have to be upheld manually. Writes are allowed only while holding the write lock (see `write`), and
+    /// reads not done under a lock must be volatile, and validated with [`version`](Self::version) (after an
+    /// `Acquire` fence) to be the same as before the read, and not `V::LOCKED`.
+    #[inline]
+    pub const fn data_ptr(&self) -> *mut T {
+        self.data.get()
+    }
+    /// Second half of an optimistic read: returns `true` if there were no writes since `read_version`
+    /// returned `version`, so the value copied with it is initialized and not torn.
+    #[inline]
+    pub fn validate_read(&self, version: V) -> bool {
+        if version == Self::LOCKED {
+            return false;
+        }
+        let valid = self.version.load(Ordering::Relaxed) == version;
+        if !valid {
+            record_torn_read();
+        }
+        valid
+    }
+    /// Last version published by an unlock (the version readers saw before the lock was last taken). A
+    /// watchdog that finds the lock locked samples this, and passes it to [`recover`](Self::recover) if the
+    /// lock is still locked later.
+    #[cfg(feature = "seqlock-recover")]
+    #[inline]
+    pub fn last_unlocked_version(&self) -> V {
+        // Acquire: the lock was taken by an RMW in the release sequence of the last unlock, so this
+        // synchronizes with it, and `last_unlocked` is up to date.
+        let _ = self.version.load(Ordering::Acquire);
+        self.last_unlocked.load(Ordering::Relaxed)
+    }
+    /// Force-unlocks a lock that is stuck in the locked state (e.g. its guard was leaked), by publishing the
+    /// successor of `expected_version`. Does nothing, and returns `false`, if the lock is not locked, or if it
+    /// was unlocked since the watchdog sampled `expected_version` with
+    /// [`last_unlocked_version`](Self::last_unlocked_version) (so the current holder is not the stuck one).
+    ///
+    /// The published version is newer than any version readers could have observed, so optimistic readers that
+    /// started before the lock got stuck don't validate data the stuck writer may have partially written.
+    ///
+    /// Remaining hazards: a read guard restores the version it found, so if the stuck-looking read guard was
+    /// dropped and a new read guard taken in the meantime, the new one can't be told apart from it, and gets
+    /// clobbered. The check also can't tell after the version wrapped around `V` (so it's weaker with `u16`).
+    ///
+    /// # Safety
+    /// No guard of this lock may be alive, or be dropped later. The value must be valid (a writer stuck in the
+    /// middle of a write might have left it partially written).
+    #[cfg(feature = "seqlock-recover")]
+    #[inline]
+    pub unsafe fn recover(&self, expected_version: V) -> bool {
+        if self.version.load(Ordering::Acquire) != Self::LOCKED {
+            return false;
+        }
+        let next = expected_version.next_version();
+        // Claims the recovery, so only one of concurrent `recover` calls publishes
+        if self
+            .last_unlocked
+            .compare_exchange(expected_version, next, Ordering::Relaxed, Ordering::Relaxed)
+            .is_err()
+        {
+            return false;
+        }
+        self.version
+            .compare_exchange(Self::LOCKED, next, Ordering::Release, Ordering::Relaxed)
+            .is_ok()
+    }
+    /// Total time the write lock was held, in nanoseconds.
+    #[cfg(feature = "stats")]
+    #[inline]
+    pub fn total_write_hold_nanos(&self) -> u64 {
+        self.stats.total_nanos()
+    }
+    /// Number of write acquisitions that had to wait for the lock.
+    #[cfg(feature = "stats")]
+    #[inline]
+    pub fn contended_write_acquisitions(&self) -> usize {
+        self.stats.contended()
+    }
+    /// Current version. It is `V::LOCKED` while the lock is held, and changes after every write.
+    #[inline]
+    pub fn version(&self, order: Ordering) -> V {
+        self.version.load(order)
+    }
     #[inline]
     pub fn store(&self, v: T) {
         *self.write() = v;
     }
 }
-impl<const B: isize, T: Default> SpinSeqLockEx<B, T> {
+impl<const B: isize, T: Default, V: SeqLockVersion, A> SpinSeqLockEx<B, T, V, A> {
     #[inline]
     pub fn take(&self) -> T {
         mem::take(&mut self.write())
     }
 }
-impl<const B: isize, T: Default> Default for SpinSeqLockEx<B, T> {
+impl<const B: isize, T: Default, V: SeqLockVersion, A> Default for SpinSeqLockEx<B, T, V, A> {
     #[inline]
     fn default() -> Self {
         Self {
             data: UnsafeCell::new(T::default()),
-            version: AtomicUsize::new(Self::INIT_UNLOCKED),
+            version: init_version::<V>(),
+            #[cfg(feature = "seqlock-recover")]
+            last_unlocked: init_version::<V>(),
+            level: LockLevel::NONE,
+            stats: HoldStats::new(),
+            _align: [],
         }
     }
 }
-impl<const B: isize, T: Copy> Clone for SpinSeqLockEx<B, T> {
+impl<const B: isize, T: Copy, V: SeqLockVersion, A> Clone for SpinSeqLockEx<B, T, V, A> {
     #[inline]
     fn clone(&self) -> Self {
         let data = self.load();
         Self::new(data)
     }
 }
-impl<const B: isize, T: Clone> SpinSeqLockEx<B, T> {
+impl<const B: isize, T: Clone, V: SeqLockVersion, A> SpinSeqLockEx<B, T, V, A> {
     #[inline]
     pub fn clone2(&self) -> Self {
         let data = self.read();
         Self::new(data.clone())
     }
 }
-impl<const B: isize, T: core::fmt::Debug + Copy> core::fmt::Debug for SpinSeqLockEx<B, T> {
+impl<const B: isize, T: core::fmt::Debug + Copy, V: SeqLockVersion, A> core::fmt::Debug
+    for SpinSeqLockEx<B, T, V, A>
+{
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         f.debug_struct("AtomicCellOpt")
             .field("data", &self.load())
             .finish()
     }
 }
-impl<const B: isize, T: Hash + Copy> Hash for SpinSeqLockEx<B, T> {
+impl<const B: isize, T: Hash + Copy, V: SeqLockVersion, A> Hash for SpinSeqLockEx<B, T, V, A> {
     #[inline]
     fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
         self.load().hash(state);
     }
 }
-impl<const B: isize, T: PartialEq + Copy> PartialEq for SpinSeqLockEx<B, T> {
+impl<const B: isize, T: PartialEq + Copy, V: SeqLockVersion, A> PartialEq
+    for SpinSeqLockEx<B, T, V, A>
+{
     #[inline]
     fn eq(&self, other: &Self) -> bool {
         self.load() == other.load()
     }
 }
-impl<const B: isize, T: Eq + Copy> Eq for SpinSeqLockEx<B, T> {}
+impl<const B: isize, T: Eq + Copy, V: SeqLockVersion, A> Eq for SpinSeqLockEx<B, T, V, A> {}
 
-impl<const B: isize, T: PartialOrd + Copy> PartialOrd for SpinSeqLockEx<B, T> {
+impl<const B: isize, T: PartialOrd + Copy, V: SeqLockVersion, A> PartialOrd
+    for SpinSeqLockEx<B, T, V, A>
+{
     #[inline]
     fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
         self.load().partial_cmp(&other.load())
     }
 }
-impl<const B: isize, T: Ord + Copy> Ord for SpinSeqLockEx<B, T> {
+impl<const B: isize, T: Ord + Copy, V: SeqLockVersion, A> Ord for SpinSeqLockEx<B, T, V, A> {
     #[inline]
     fn cmp(&self, other: &Self) -> core::cmp::Ordering {
         if core::ptr::eq(self, other) {
@@ -273,21 +960,543 @@ impl<const B: isize, T: Ord + Copy> Ord for SpinSeqLockEx<B, T> {
 mod ser_de {
     use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
-    use crate::spin_seqlock::SpinSeqLockEx;
-    impl<const B: isize, T: Serialize + Copy> Serialize for SpinSeqLockEx<B, T> {
+    use crate::spin_seqlock::{SeqLockVersion, SpinSeqLockEx};
+    impl<const B: isize, T: Serialize + Copy, V: SeqLockVersion, A> Serialize
+        for SpinSeqLockEx<B, T, V, A>
+    {
         fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
             self.load().serialize(serializer)
         }
     }
-    impl<'a, const B: isize, T: Deserialize<'a> + Copy> Deserialize<'a> for SpinSeqLockEx<B, T> {
+    impl<'a, const B: isize, T: Deserialize<'a> + Copy, V: SeqLockVersion, A> Deserialize<'a>
+        for SpinSeqLockEx<B, T, V, A>
+    {
         fn deserialize<D: Deserializer<'a>>(deserializer: D) -> Result<Self, D::Error> {
             Ok(Self::new(T::deserialize(deserializer)?))
         }
     }
 }
-impl<const B: isize, T: Copy> From<T> for SpinSeqLockEx<B, T> {
+impl<const B: isize, T: Copy, V: SeqLockVersion, A> From<T> for SpinSeqLockEx<B, T, V, A> {
     #[inline]
     fn from(value: T) -> Self {
         Self::new(value)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replace_versioned_increments() {
+        let lock = SpinSeqLock::new(0u32);
+        let mut last = lock.version(Ordering::Acquire);
+        for i in 1..=5 {
+            let (prev, version) = lock.replace_versioned(i);
+            assert_eq!(prev, i - 1);
+            assert!(version > last);
+            assert_eq!(version, lock.version(Ordering::Acquire));
+            last = version;
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "optimistic reads are disabled under miri")]
+    fn read_into_large_struct() {
+        let lock = SpinSeqLock::new([7u64; 64]);
+        let mut dst = MaybeUninit::uninit();
+        assert!(lock.read_into(&mut dst));
+        // Safety: `read_into` returned true
+        assert_eq!(unsafe { dst.assume_init() }, [7; 64]);
+
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                for i in 0..1000 {
+                    lock.store([i; 64]);
+                }
+            });
+            for _ in 0..1000 {
+                let mut dst = MaybeUninit::uninit();
+                if lock.read_into(&mut dst) {
+                    // Safety: `read_into` returned true
+                    let value = unsafe { dst.assume_init() };
+                    assert!(value.iter().all(|&v| v == value[0]));
+                }
+            }
+        });
+    }
+
+    #[test]
+    fn write_hook_sees_final_value_before_publish() {
+        let lock = SpinSeqLock::new(0u32);
+        let before = lock.version(Ordering::Relaxed);
+        let mut seen = None;
+        {
+            let mut guard = lock.write_with_hook(|v| {
+                seen = Some((*v, lock.version(Ordering::Relaxed)));
+            });
+            *guard = 1;
+            *guard += 1;
+        }
+        assert_eq!(seen, Some((2, SpinSeqLock::<u32>::LOCKED)));
+        assert_eq!(lock.version(Ordering::Relaxed), before.next_version());
+        assert_eq!(lock.load(), 2);
+    }
+
+    #[test]
+    fn write_hook_panic_releases_lock() {
+        let lock = SpinSeqLock::new(0u32);
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let mut guard = lock.write_with_hook(|_| panic!("audit failed"));
+            *guard = 1;
+        }));
+        assert!(result.is_err());
+        assert!(lock.try_write().is_some());
+        assert_eq!(lock.load(), 1);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "optimistic reads are disabled under miri")]
+    fn read_coherent_conflict() {
+        let a = SpinSeqLock::new(1u32);
+        let b = SpinSeqLockEx::<DEFAULT_SPIN_LIMIT, u64, u16>::new(2);
+        assert_eq!(read_coherent(&a, &b), Some((1, 2)));
+        {
+            let mut guard = b.write();
+            *guard = 3;
+            assert_eq!(read_coherent(&a, &b), None);
+        }
+        assert_eq!(read_coherent(&a, &b), Some((1, 3)));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "optimistic reads are disabled under miri")]
+    fn read_coherent_aligned_locks() {
+        use crate::align::{Align64, Align128};
+        let a = SpinSeqLockEx::<DEFAULT_SPIN_LIMIT, u32, usize, Align64>::new(1);
+        let b = SpinSeqLockEx::<DEFAULT_SPIN_LIMIT, [u8; 3], u16, Align128>::new([2; 3]);
+        assert_eq!(read_coherent(&a, &b), Some((1, [2; 3])));
+        a.store(4);
+        assert_eq!(read_coherent(&a, &b), Some((4, [2; 3])));
+    }
+
+    #[test]
+    fn next_version_skips_locked() {
+        assert_eq!(u16::MAX.next_version(), 1);
+        assert_eq!(u32::MAX.next_version(), 1);
+        assert_eq!(1u16.next_version(), 2);
+    }
+
+    #[test]
+    fn u16_version_wraparound() {
+        let lock = SpinSeqLockEx::<DEFAULT_SPIN_LIMIT, u32, u16>::new(0);
+        lock.version.store(u16::MAX - 1, Ordering::Relaxed);
+        lock.store(1);
+        assert_eq!(lock.version(Ordering::Relaxed), u16::MAX);
+        lock.store(2);
+        assert_eq!(lock.version(Ordering::Relaxed), 1);
+        assert_eq!(lock.load(), 2);
+        assert_eq!(lock.replace_versioned(3), (2, 2));
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn snapshot_all_small_array() {
+        let locks: [SpinSeqLock<u32>; 4] = core::array::from_fn(|i| SpinSeqLock::new(i as u32));
+        locks[2].store(20);
+        assert_eq!(snapshot_all(&locks), [0, 1, 20, 3]);
+        assert!(snapshot_all::<DEFAULT_SPIN_LIMIT, u32, usize, ()>(&[]).is_empty());
+    }
+
+    #[cfg(feature = "seqlock-debug")]
+    #[test]
+    #[cfg_attr(miri, ignore = "optimistic reads are disabled under miri")]
+    fn torn_reads_are_counted() {
+        use std::time::{Duration, Instant};
+
+        let lock = SpinSeqLock::new([0u64; 512]);
+        let done = core::sync::atomic::AtomicBool::new(false);
+        let before = torn_read_count();
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                let mut i = 0;
+                while !done.load(Ordering::Relaxed) {
+                    i += 1;
+                    lock.store([i; 512]);
+                }
+            });
+            let deadline = Instant::now() + Duration::from_secs(10);
+            while torn_read_count() == before && Instant::now() < deadline {
+                let value = lock.load();
+                assert!(value.iter().all(|&v| v == value[0]));
+            }
+            done.store(true, Ordering::Relaxed);
+        });
+        assert!(torn_read_count() > before);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "optimistic reads are disabled under miri")]
+    fn data_ptr_single_field() {
+        #[derive(Clone, Copy)]
+        struct Pair {
+            _big: [u64; 16],
+            small: u32,
+        }
+        let lock = SpinSeqLock::new(Pair {
+            _big: [0; 16],
+            small: 3,
+        });
+        lock.write().small = 4;
+        let small = loop {
+            let version = lock.version(Ordering::Acquire);
+            if version == SpinSeqLock::<Pair>::LOCKED {
+                continue;
+            }
+            // Safety: volatile read of a field, validated against the version below
+            let small = unsafe { ptr::read_volatile(&raw const (*lock.data_ptr()).small) };
+            fence(Ordering::Acquire);
+            if lock.version(Ordering::Relaxed) == version {
+                break small;
+            }
+        };
+        assert_eq!(small, 4);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    #[cfg_attr(miri, ignore = "optimistic reads are disabled under miri")]
+    fn load_timeout_with_long_holding_writer() {
+        use std::time::{Duration, Instant};
+
+        let lock = SpinSeqLock::new(1u32);
+        {
+            let _guard = lock.write();
+            let start = Instant::now();
+            assert_eq!(lock.load_timeout(Duration::from_millis(5)), None);
+            assert!(start.elapsed() >= Duration::from_millis(5));
+        }
+        assert_eq!(lock.load_timeout(Duration::ZERO), Some(1));
+    }
+
+    #[test]
+    fn swap_atomic_concurrent() {
+        const THREADS: u32 = 4;
+        const SWAPS: u32 = 500;
+        let lock = SpinSeqLock::new((0u32, 0u32));
+        let mut seen: std::vec::Vec<u32> = std::thread::scope(|s| {
+            let swappers: std::vec::Vec<_> = (0..THREADS)
+                .map(|t| {
+                    let lock = &lock;
+                    s.spawn(move || {
+                        (1..=SWAPS)
+                            .map(|i| {
+                                let value = t * SWAPS + i;
+                                let (a, b) = lock.swap_atomic((value, value));
+                                assert_eq!(a, b);
+                                a
+                            })
+                            .collect::<std::vec::Vec<_>>()
+                    })
+                })
+                .collect();
+            for _ in 0..1000 {
+                let (a, b) = lock.load();
+                assert_eq!(a, b);
+            }
+            swappers
+                .into_iter()
+                .flat_map(|h| h.join().unwrap())
+                .collect()
+        });
+        seen.push(lock.load().0);
+        seen.sort_unstable();
+        // Every value was returned by exactly one swap (or is the final one)
+        assert!(seen.iter().copied().eq(0..=THREADS * SWAPS));
+    }
+
+    #[test]
+    fn fetch_min_max_tuples() {
+        let lock = SpinSeqLock::new((3u32, 5u32));
+        let version = lock.version(Ordering::Acquire);
+        assert_eq!(lock.fetch_max((3, 4)), (3, 5));
+        assert_eq!(lock.fetch_min((4, 0)), (3, 5));
+        assert_eq!(lock.version(Ordering::Acquire), version);
+
+        assert_eq!(lock.fetch_max((4, 0)), (3, 5));
+        assert_eq!(lock.load(), (4, 0));
+        assert_eq!(lock.fetch_min((3, 9)), (4, 0));
+        assert_eq!(lock.load(), (3, 9));
+        assert_ne!(lock.version(Ordering::Acquire), version);
+    }
+
+    #[cfg(feature = "seqlock-recover")]
+    #[test]
+    fn recover_publishes_newer_version() {
+        let lock = SpinSeqLock::new(0u32);
+        let unlocked = lock.last_unlocked_version();
+        // Safety: the lock is not locked
+        assert!(!unsafe { lock.recover(unlocked) });
+        assert_eq!(lock.load(), 0);
+
+        let mut seen = vec![lock.version(Ordering::Acquire)];
+        for i in 1..=3 {
+            lock.store(i);
+            seen.push(lock.version(Ordering::Acquire));
+        }
+        drop(lock.read());
+        let (version, _) = lock.read_version();
+        mem::forget(lock.write());
+        assert_eq!(lock.version(Ordering::Acquire), SpinSeqLock::<u32>::LOCKED);
+        assert!(!lock.validate_read(version));
+
+        let sampled = lock.last_unlocked_version();
+        assert_eq!(sampled, *seen.last().unwrap());
+        // Safety: the leaked guard is never dropped, and the value is valid
+        assert!(unsafe { lock.recover(sampled) });
+        let recovered = lock.version(Ordering::Acquire);
+        assert!(!seen.contains(&recovered));
+        assert_eq!(recovered, sampled.next_version());
+        assert!(!lock.validate_read(version));
+        assert_eq!(lock.load(), 3);
+
+        lock.store(4);
+        assert_eq!(lock.version(Ordering::Acquire), recovered.next_version());
+    }
+
+    #[cfg(feature = "seqlock-recover")]
+    #[test]
+    fn recover_rejects_stale_version() {
+        let lock = SpinSeqLock::new(0u32);
+        mem::forget(lock.write());
+        let sampled = lock.last_unlocked_version();
+        // Safety: the leaked guard is never dropped, and the value is valid
+        assert!(unsafe { lock.recover(sampled) });
+
+        // The watchdog's sample is from before the lock was unlocked, and taken again by a live writer
+        let mut guard = lock.write();
+        // Safety: `recover` returns false without touching the lock, so the live guard stays valid
+        assert!(!unsafe { lock.recover(sampled) });
+        assert_eq!(lock.version(Ordering::Acquire), SpinSeqLock::<u32>::LOCKED);
+        *guard = 1;
+        drop(guard);
+        assert_eq!(lock.load(), 1);
+        assert_eq!(
+            lock.version(Ordering::Acquire),
+            sampled.next_version().next_version()
+        );
+    }
+
+    #[test]
+    fn read_scoped_non_copy() {
+        let lock = SpinSeqLock::new(([0u64; 8], String::from("payload")));
+        assert_eq!(lock.read_scoped(|(_, name)| name.len()), 7);
+
+        // Safety: only the plain data field is read, by value
+        let read_ids =
+            |p: *const ([u64; 8], String)| unsafe { ptr::read_volatile(&raw const (*p).0) };
+        assert_eq!(lock.read_scoped_optimistic(read_ids), [0; 8]);
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                for i in 1..=200 {
+                    lock.write().0 = [i; 8];
+                }
+            });
+            for _ in 0..200 {
+                let ids = lock.read_scoped_optimistic(read_ids);
+                assert!(ids.iter().all(|&id| id == ids[0]), "torn read: {ids:?}");
+            }
+        });
+        assert_eq!(lock.read_scoped_optimistic(read_ids), [200; 8]);
+        assert_eq!(lock.read_scoped(|(_, name)| name.clone()), "payload");
+    }
+
+    #[test]
+    fn zero_sized_token() {
+        let lock = SpinSeqLock::new(());
+        let version = lock.version(Ordering::Acquire);
+        lock.load();
+        lock.store(());
+        assert_eq!(lock.version(Ordering::Acquire), version.next_version());
+        let mut dst = MaybeUninit::uninit();
+        #[cfg(not(miri))]
+        assert!(lock.read_into(&mut dst));
+        #[cfg(miri)]
+        let _ = lock.read_into(&mut dst);
+        lock.access(|()| ());
+        std::thread::scope(|s| {
+            for _ in 0..2 {
+                s.spawn(|| {
+                    for _ in 0..100 {
+                        lock.store(());
+                        lock.load();
+                    }
+                });
+            }
+        });
+    }
+
+    #[test]
+    fn load_projection_large_struct() {
+        #[derive(Clone, Copy, Debug, PartialEq)]
+        struct Limits {
+            limit: u32,
+            timeout: u32,
+        }
+        #[derive(Clone, Copy)]
+        struct Config {
+            table: [u64; 256],
+            limits: Limits,
+        }
+        let lock = SpinSeqLock::new(Config {
+            table: [0; 256],
+            limits: Limits {
+                limit: 0,
+                timeout: 0,
+            },
+        });
+        // Safety: projects to a field, without reading through the pointer
+        let limits = || unsafe { lock.load_projection(|c| &raw const (*c).limits) };
+        assert_eq!(limits().limit, 0);
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                for i in 1..=500 {
+                    let mut config = lock.write();
+                    config.limits.limit = i;
+                    config.limits.timeout = i * 2;
+                }
+            });
+            for _ in 0..500 {
+                let Limits { limit, timeout } = limits();
+                assert_eq!(timeout, limit * 2);
+            }
+        });
+        assert_eq!(
+            limits(),
+            Limits {
+                limit: 500,
+                timeout: 1000
+            }
+        );
+        // Safety: same as above
+        let last = unsafe { lock.load_projection(|c| &raw const (*c).table[255]) };
+        assert_eq!(last, 0);
+    }
+
+    #[test]
+    fn init_in_place_large() {
+        let mut slot = MaybeUninit::<SpinSeqLock<[u8; 4096]>>::uninit();
+        // Safety: `slot` is valid for writes and aligned
+        unsafe { SpinSeqLock::init_in_place(slot.as_mut_ptr(), [7; 4096]) };
+        // Safety: fully initialized by `init_in_place`
+        let lock = unsafe { slot.assume_init_ref() };
+        lock.write()[4095] = 1;
+        let guard = lock.read();
+        assert_eq!(guard[0], 7);
+        assert_eq!(guard[4095], 1);
+    }
+
+    #[test]
+    fn replace_versioned_epochs_across_threads() {
+        let lock = SpinSeqLock::new(0usize);
+        let start = lock.version(Ordering::Acquire);
+        let mut swaps: Vec<(usize, usize, usize)> = std::thread::scope(|s| {
+            let handles: Vec<_> = (0..4)
+                .map(|t| {
+                    let lock = &lock;
+                    s.spawn(move || {
+                        (1..=100)
+                            .map(|i| {
+                                let new = t * 100 + i;
+                                let (old, epoch) = lock.replace_versioned(new);
+                                (epoch, old, new)
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|h| h.join().unwrap())
+                .collect()
+        });
+        // Ordered by epoch, every swap returns the value installed by the previous one.
+        swaps.sort_unstable();
+        let mut expected_old = 0;
+        for (i, &(epoch, old, new)) in swaps.iter().enumerate() {
+            assert_eq!(epoch, start + i + 1);
+            assert_eq!(old, expected_old);
+            expected_old = new;
+        }
+        assert_eq!(lock.load(), expected_old);
+    }
+
+    #[test]
+    fn compare_exchange_match_mismatch() {
+        let lock = SpinSeqLock::new([1u64; 8]);
+        let version = lock.version(Ordering::Acquire);
+        assert_eq!(lock.compare_exchange([2; 8], [3; 8]), Err([1; 8]));
+        assert_eq!(lock.version(Ordering::Acquire), version);
+        assert_eq!(lock.load(), [1; 8]);
+
+        assert_eq!(lock.compare_exchange([1; 8], [3; 8]), Ok([1; 8]));
+        assert_eq!(lock.version(Ordering::Acquire), version.next_version());
+        assert_eq!(lock.load(), [3; 8]);
+    }
+
+    #[test]
+    fn update_if_bumps_only_when_applied() {
+        let lock = SpinSeqLock::new(vec![1u32]);
+        let version = lock.version(Ordering::Acquire);
+        assert!(!lock.update_if(|v| v.len() > 1, |v| v.clear()));
+        assert_eq!(lock.version(Ordering::Acquire), version);
+        assert_eq!(lock.access(Vec::len), 1);
+
+        assert!(lock.update_if(|v| v.len() == 1, |v| v.push(2)));
+        assert_eq!(lock.version(Ordering::Acquire), version.next_version());
+        assert_eq!(lock.access(Vec::clone), [1, 2]);
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn try_into_inner_arc() {
+        use std::sync::Arc;
+
+        let lock = Arc::new(SpinSeqLock::new(5u32));
+        let other = Arc::clone(&lock);
+        let Err(lock) = lock.try_into_inner() else {
+            panic!("shared Arc was unwrapped");
+        };
+        assert!(Arc::ptr_eq(&lock, &other));
+        drop(other);
+        assert_eq!(lock.try_into_inner().ok(), Some(5));
+    }
+
+    #[test]
+    fn try_read_try_write_fail_while_locked() {
+        let lock = SpinSeqLock::new(0u32);
+        let read = lock.try_read().unwrap();
+        // Read guard locks exclusively too
+        assert!(lock.try_read().is_none());
+        assert!(lock.try_write().is_none());
+        drop(read);
+
+        let write = lock.try_write().unwrap();
+        assert!(lock.try_read().is_none());
+        assert!(lock.try_write().is_none());
+        drop(write);
+        assert_eq!(*lock.try_read().unwrap(), 0);
+    }
+
+    #[test]
+    fn try_write_advances_version() {
+        let lock = SpinSeqLock::new(0u32);
+        let version = lock.version(Ordering::Acquire);
+        drop(lock.try_read().unwrap());
+        assert_eq!(lock.version(Ordering::Acquire), version);
+
+        *lock.try_write().unwrap() = 1;
+        assert_eq!(lock.version(Ordering::Acquire), version.next_version());
+        assert_eq!(lock.load(), 1);
+    }
+}
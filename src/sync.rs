@@ -0,0 +1,44 @@
+//! Atomics used by `SpinMutex`, `SpinRwLock`, `SpinSeqLock` and `atomic_t` (without `portable-atomic`). When
+//! built with `--cfg loom`, they are replaced by `loom`'s, so code built on them can be model-checked.
+
+#[cfg(all(not(loom), not(feature = "portable-atomic")))]
+pub(crate) use core::sync::atomic::AtomicU8;
+#[cfg(all(target_has_atomic = "64", not(loom)))]
+pub(crate) use core::sync::atomic::AtomicU64;
+#[cfg(not(loom))]
+pub(crate) use core::sync::atomic::{
+    AtomicBool, AtomicIsize, AtomicU16, AtomicU32, AtomicUsize, fence,
+};
+#[cfg(all(loom, not(feature = "portable-atomic")))]
+pub(crate) use loom::sync::atomic::AtomicU8;
+#[cfg(loom)]
+pub(crate) use loom::sync::atomic::{
+    AtomicBool, AtomicIsize, AtomicU16, AtomicU32, AtomicU64, AtomicUsize, fence,
+};
+
+/// `const fn`, except under `loom`, since `loom` atomics can't be created in const context.
+macro_rules! loom_const_fn {
+    ($(#[$attr:meta])* $vis:vis fn $($rest:tt)*) => {
+        #[cfg(not(loom))]
+        $(#[$attr])* $vis const fn $($rest)*
+        #[cfg(loom)]
+        $(#[$attr])* $vis fn $($rest)*
+    };
+    ($(#[$attr:meta])* $vis:vis unsafe fn $($rest:tt)*) => {
+        #[cfg(not(loom))]
+        $(#[$attr])* $vis const unsafe fn $($rest)*
+        #[cfg(loom)]
+        $(#[$attr])* $vis unsafe fn $($rest)*
+    };
+}
+pub(crate) use loom_const_fn;
+
+/// `loom` only switches threads at atomic operations and yields, so under `loom` spinning yields too.
+#[cfg(any(feature = "std", loom))]
+#[inline(always)]
+pub(crate) fn yield_now() {
+    #[cfg(loom)]
+    loom::thread::yield_now();
+    #[cfg(not(loom))]
+    std::thread::yield_now();
+}
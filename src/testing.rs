@@ -0,0 +1,194 @@
+//! Helpers for stress-testing locks built on this crate.
+//!
+//! With the `stats` feature, `hammer_lock` also reports how long the lock was write-held and how many write
+//! acquisitions were contended during the run, see [`LockStats`].
+use std::{
+    sync::Barrier,
+    thread,
+    time::{Duration, Instant},
+};
+
+#[derive(Debug, Clone, Copy)]
+pub struct HammerStats {
+    pub threads: usize,
+    pub iters: usize,
+    /// Wall-clock time from the moment all threads were released until the last one finished.
+    pub elapsed: Duration,
+    pub fastest_thread: Duration,
+    pub slowest_thread: Duration,
+    /// Time the lock was write-held during the run. Only set by `hammer_lock`.
+    #[cfg(feature = "stats")]
+    pub write_hold: Option<Duration>,
+    /// Write acquisitions that had to wait for the lock during the run. Only set by `hammer_lock`.
+    #[cfg(feature = "stats")]
+    pub contended_acquisitions: Option<usize>,
+}
+impl HammerStats {
+    #[inline]
+    pub fn total_ops(&self) -> usize {
+        self.threads * self.iters
+    }
+    #[inline]
+    pub fn ops_per_sec(&self) -> f64 {
+        self.total_ops() as f64 / self.elapsed.as_secs_f64()
+    }
+    /// Ratio between the slowest and the fastest thread. Values far above 1 indicate unfair contention.
+    ///
+    /// `None` if no threads were spawned, or the fastest thread finished too quickly to be measured.
+    #[inline]
+    pub fn imbalance(&self) -> Option<f64> {
+        if self.threads == 0 || self.fastest_thread.is_zero() {
+            return None;
+        }
+        Some(self.slowest_thread.as_secs_f64() / self.fastest_thread.as_secs_f64())
+    }
+}
+
+/// Spawns `threads` threads that all start at the same time and call `op(lock)` `iters` times each.
+pub fn hammer<L: Sync>(
+    lock: &L,
+    threads: usize,
+    iters: usize,
+    op: impl Fn(&L) + Sync,
+) -> HammerStats {
+    let barrier = Barrier::new(threads + 1);
+    let (start, thread_times) = thread::scope(|s| {
+        let handles: Vec<_> = (0..threads)
+            .map(|_| {
+                s.spawn(|| {
+                    barrier.wait();
+                    let start = Instant::now();
+                    for _ in 0..iters {
+                        op(lock);
+                    }
+                    start.elapsed()
+                })
+            })
+            .collect();
+        barrier.wait();
+        let start = Instant::now();
+        let thread_times: Vec<Duration> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+        (start, thread_times)
+    });
+    HammerStats {
+        threads,
+        iters,
+        elapsed: start.elapsed(),
+        fastest_thread: thread_times.iter().copied().min().unwrap_or_default(),
+        slowest_thread: thread_times.iter().copied().max().unwrap_or_default(),
+        #[cfg(feature = "stats")]
+        write_hold: None,
+        #[cfg(feature = "stats")]
+        contended_acquisitions: None,
+    }
+}
+
+/// Counters of the `stats` feature, for `hammer_lock`.
+#[cfg(feature = "stats")]
+pub trait LockStats {
+    fn total_write_hold_nanos(&self) -> u64;
+    fn contended_write_acquisitions(&self) -> usize;
+}
+#[cfg(feature = "stats")]
+impl<const S: isize, T, A> LockStats for crate::spin_mutex::SpinMutexEx<S, T, A> {
+    #[inline]
+    fn total_write_hold_nanos(&self) -> u64 {
+        self.total_write_hold_nanos()
+    }
+    #[inline]
+    fn contended_write_acquisitions(&self) -> usize {
+        self.contended_write_acquisitions()
+    }
+}
+#[cfg(feature = "stats")]
+impl<const S: isize, T, const R: isize, A, const W: usize> LockStats
+    for crate::spin_rwlock::SpinRwLockEx<S, T, R, A, W>
+{
+    #[inline]
+    fn total_write_hold_nanos(&self) -> u64 {
+        self.total_write_hold_nanos()
+    }
+    #[inline]
+    fn contended_write_acquisitions(&self) -> usize {
+        self.contended_write_acquisitions()
+    }
+}
+#[cfg(feature = "stats")]
+impl<const B: isize, T, V: crate::spin_seqlock::SeqLockVersion, A> LockStats
+    for crate::spin_seqlock::SpinSeqLockEx<B, T, V, A>
+{
+    #[inline]
+    fn total_write_hold_nanos(&self) -> u64 {
+        self.total_write_hold_nanos()
+    }
+    #[inline]
+    fn contended_write_acquisitions(&self) -> usize {
+        self.contended_write_acquisitions()
+    }
+}
+
+/// Same as `hammer`, but also fills in `write_hold` and `contended_acquisitions`, from the difference of the
+/// lock's counters before and after the run.
+#[cfg(feature = "stats")]
+pub fn hammer_lock<L: LockStats + Sync>(
+    lock: &L,
+    threads: usize,
+    iters: usize,
+    op: impl Fn(&L) + Sync,
+) -> HammerStats {
+    let hold_before = lock.total_write_hold_nanos();
+    let contended_before = lock.contended_write_acquisitions();
+    let stats = hammer(lock, threads, iters, op);
+    HammerStats {
+        write_hold: Some(Duration::from_nanos(
+            lock.total_write_hold_nanos() - hold_before,
+        )),
+        contended_acquisitions: Some(lock.contended_write_acquisitions() - contended_before),
+        ..stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spin_mutex::SpinMutex;
+
+    #[test]
+    fn hammer_spin_mutex() {
+        let mutex = SpinMutex::new(0usize);
+        let stats = hammer(&mutex, 4, 1000, |m| *m.lock() += 1);
+        assert_eq!(*mutex.lock(), 4000);
+        assert_eq!(stats.total_ops(), 4000);
+        assert!(stats.slowest_thread >= stats.fastest_thread);
+        if let Some(imbalance) = stats.imbalance() {
+            assert!(imbalance >= 1.0);
+        }
+    }
+
+    #[test]
+    fn imbalance_without_threads() {
+        let stats = hammer(&(), 0, 1000, |_| {});
+        assert_eq!(stats.total_ops(), 0);
+        assert_eq!(stats.imbalance(), None);
+    }
+
+    #[cfg(feature = "stats")]
+    #[test]
+    fn hammer_lock_reports_lock_stats() {
+        let mutex = SpinMutex::new(0usize);
+        *mutex.lock() += 1;
+        let stats = hammer_lock(&mutex, 4, 1000, |m| {
+            let mut guard = m.lock();
+            *guard += 1;
+            std::hint::black_box(&mut *guard);
+        });
+        assert_eq!(*mutex.lock(), 4001);
+        let write_hold = stats.write_hold.unwrap();
+        assert!(write_hold <= Duration::from_nanos(mutex.total_write_hold_nanos()));
+        assert!(stats.contended_acquisitions.unwrap() <= stats.total_ops());
+
+        let plain = hammer(&mutex, 1, 10, |m| *m.lock() += 1);
+        assert_eq!(plain.write_hold, None);
+        assert_eq!(plain.contended_acquisitions, None);
+    }
+}
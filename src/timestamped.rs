@@ -0,0 +1,97 @@
+use crate::{backoff::DEFAULT_SPIN_LIMIT, spin_seqlock::SpinSeqLockEx, sync::loom_const_fn};
+
+pub type Timestamped<T> = TimestampedEx<DEFAULT_SPIN_LIMIT, T>;
+
+/// Value paired with a sequence number, that is incremented by every `store`.
+///
+/// Value and its sequence number are published together, under a single seqlock write.
+pub struct TimestampedEx<const B: isize, T> {
+    lock: SpinSeqLockEx<B, (T, u64)>,
+}
+impl<const B: isize, T> TimestampedEx<B, T> {
+    loom_const_fn! {
+        /// Starts with sequence number 0.
+        #[inline]
+        pub fn new(val: T) -> Self {
+            Self {
+                lock: SpinSeqLockEx::new((val, 0)),
+            }
+        }
+    }
+    /// Stores `val`, and returns its sequence number.
+    #[inline]
+    pub fn store(&self, val: T) -> u64 {
+        let mut guard = self.lock.write();
+        let timestamp = guard.1 + 1;
+        *guard = (val, timestamp);
+        timestamp
+    }
+    #[inline]
+    pub fn into_inner(self) -> (T, u64) {
+        self.lock.into_inner()
+    }
+}
+impl<const B: isize, T: Copy> TimestampedEx<B, T> {
+    /// Returns the value with its sequence number.
+    #[inline]
+    pub fn load(&self) -> (T, u64) {
+        self.lock.load()
+    }
+}
+impl<const B: isize, T: Default> Default for TimestampedEx<B, T> {
+    #[inline]
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+impl<const B: isize, T: core::fmt::Debug + Copy> core::fmt::Debug for TimestampedEx<B, T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let (data, timestamp) = self.load();
+        f.debug_struct("Timestamped")
+            .field("data", &data)
+            .field("timestamp", &timestamp)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timestamp_strictly_increases() {
+        let cell = Timestamped::new(0u32);
+        assert_eq!(cell.load(), (0, 0));
+        let mut last = 0;
+        for i in 1..=10 {
+            let timestamp = cell.store(i * 2);
+            assert!(timestamp > last);
+            assert_eq!(cell.load(), (i * 2, timestamp));
+            last = timestamp;
+        }
+        assert_eq!(cell.into_inner(), (20, 10));
+    }
+
+    #[test]
+    fn concurrent_stores_get_unique_timestamps() {
+        let cell = Timestamped::new(0u32);
+        let cell = &cell;
+        let mut timestamps: Vec<u64> = std::thread::scope(|s| {
+            let handles: Vec<_> = (0..4)
+                .map(|t| {
+                    s.spawn(move || {
+                        (0..100)
+                            .map(|i| cell.store(t * 100 + i))
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|h| h.join().unwrap())
+                .collect()
+        });
+        timestamps.sort_unstable();
+        assert_eq!(timestamps, (1..=400).collect::<Vec<_>>());
+    }
+}
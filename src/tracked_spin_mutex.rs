@@ -0,0 +1,126 @@
+use core::{
+    cell::Cell,
+    ops::{Deref, DerefMut},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use crate::{
+    backoff::DEFAULT_SPIN_LIMIT,
+    spin_mutex::{SpinMutexEx, SpinMutexGuardEx},
+};
+
+pub type TrackedSpinMutex<T> = TrackedSpinMutexEx<DEFAULT_SPIN_LIMIT, T>;
+pub type TrackedSpinMutexGuard<'a, T> = TrackedSpinMutexGuardEx<'a, DEFAULT_SPIN_LIMIT, T>;
+
+const NO_OWNER: u64 = 0;
+static NEXT_THREAD_ID: AtomicU64 = AtomicU64::new(NO_OWNER + 1);
+std::thread_local! {
+    static THREAD_ID: Cell<u64> = const { Cell::new(NO_OWNER) };
+}
+fn current_thread_id() -> u64 {
+    THREAD_ID.with(|id| {
+        if id.get() == NO_OWNER {
+            id.set(NEXT_THREAD_ID.fetch_add(1, Ordering::Relaxed));
+        }
+        id.get()
+    })
+}
+
+/// `SpinMutex` that records the thread holding it, so code can assert it holds the lock with
+/// [`is_held_by_current_thread`](Self::is_held_by_current_thread).
+///
+/// It is not reentrant: locking it twice from the same thread still deadlocks.
+pub struct TrackedSpinMutexEx<const S: isize, T> {
+    lock: SpinMutexEx<S, T>,
+    owner: AtomicU64,
+}
+pub struct TrackedSpinMutexGuardEx<'a, const S: isize, T> {
+    guard: SpinMutexGuardEx<'a, S, T>,
+    owner: &'a AtomicU64,
+}
+impl<const S: isize, T> Drop for TrackedSpinMutexGuardEx<'_, S, T> {
+    #[inline]
+    fn drop(&mut self) {
+        // `guard` field unlocks the mutex after this
+        self.owner.store(NO_OWNER, Ordering::Relaxed);
+    }
+}
+impl<const S: isize, T> Deref for TrackedSpinMutexGuardEx<'_, S, T> {
+    type Target = T;
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.guard
+    }
+}
+impl<const S: isize, T> DerefMut for TrackedSpinMutexGuardEx<'_, S, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.guard
+    }
+}
+impl<const S: isize, T: Default> Default for TrackedSpinMutexEx<S, T> {
+    #[inline]
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+impl<const S: isize, T> TrackedSpinMutexEx<S, T> {
+    #[inline]
+    pub fn new(val: T) -> Self {
+        Self {
+            lock: SpinMutexEx::new(val),
+            owner: AtomicU64::new(NO_OWNER),
+        }
+    }
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.lock.into_inner()
+    }
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.lock.get_mut()
+    }
+    #[inline]
+    pub fn lock(&self) -> TrackedSpinMutexGuardEx<'_, S, T> {
+        let guard = self.lock.lock();
+        self.owner.store(current_thread_id(), Ordering::Relaxed);
+        TrackedSpinMutexGuardEx {
+            guard,
+            owner: &self.owner,
+        }
+    }
+    /// Returns `true` if the current thread holds a guard of this mutex.
+    #[inline]
+    pub fn is_held_by_current_thread(&self) -> bool {
+        // Only the current thread can store its own id, so `Relaxed` is enough to see it.
+        self.owner.load(Ordering::Relaxed) == current_thread_id()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn held_inside_guard_scope() {
+        let mutex = TrackedSpinMutex::new(0);
+        assert!(!mutex.is_held_by_current_thread());
+        {
+            let mut guard = mutex.lock();
+            *guard += 1;
+            assert!(mutex.is_held_by_current_thread());
+        }
+        assert!(!mutex.is_held_by_current_thread());
+    }
+
+    #[test]
+    fn not_held_by_other_thread() {
+        let mutex = TrackedSpinMutex::new(0);
+        let _guard = mutex.lock();
+        std::thread::scope(|s| {
+            let held = s.spawn(|| mutex.is_held_by_current_thread()).join();
+            assert!(!held.unwrap());
+        });
+        assert!(mutex.is_held_by_current_thread());
+    }
+}
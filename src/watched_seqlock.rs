@@ -0,0 +1,136 @@
+use core::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Condvar, Mutex, PoisonError};
+
+use crate::{backoff::DEFAULT_SPIN_LIMIT, spin_seqlock::SpinSeqLockEx, sync::loom_const_fn};
+
+pub type WatchedSeqLock<T> = WatchedSeqLockEx<DEFAULT_SPIN_LIMIT, T>;
+
+/// Watch-channel primitive built on `SpinSeqLockEx`.
+///
+/// Every write bumps a notify generation and wakes up the threads blocked in
+/// [`wait_for_change`](Self::wait_for_change).
+pub struct WatchedSeqLockEx<const B: isize, T> {
+    lock: SpinSeqLockEx<B, T>,
+    generation: AtomicUsize,
+    waiters: Mutex<()>,
+    changed: Condvar,
+}
+
+impl<const B: isize, T> WatchedSeqLockEx<B, T> {
+    loom_const_fn! {
+        #[inline]
+        pub fn new(val: T) -> Self {
+            Self {
+                lock: SpinSeqLockEx::new(val),
+                generation: AtomicUsize::new(0),
+                waiters: Mutex::new(()),
+                changed: Condvar::new(),
+            }
+        }
+    }
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.lock.into_inner()
+    }
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.lock.get_mut()
+    }
+    /// Current notify generation. Pass it to `wait_for_change` to wait for the next write.
+    #[inline]
+    pub fn generation(&self) -> usize {
+        self.generation.load(Ordering::Acquire)
+    }
+    /// Blocks until the generation differs from `last_seen`, and returns the new generation.
+    pub fn wait_for_change(&self, last_seen: usize) -> usize {
+        let mut waiters = self.waiters.lock().unwrap_or_else(PoisonError::into_inner);
+        loop {
+            let generation = self.generation.load(Ordering::Acquire);
+            if generation != last_seen {
+                return generation;
+            }
+            waiters = self
+                .changed
+                .wait(waiters)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+    #[inline]
+    pub fn access<R>(&self, callback: impl FnOnce(&T) -> R) -> R {
+        self.lock.access(callback)
+    }
+    #[inline]
+    pub fn access_mut<R>(&self, callback: impl FnOnce(&mut T) -> R) -> R {
+        let result = self.lock.access_mut(callback);
+        self.notify();
+        result
+    }
+    #[inline]
+    pub fn store(&self, v: T) {
+        self.lock.store(v);
+        self.notify();
+    }
+    #[inline]
+    pub fn replace(&self, other: T) -> T {
+        let prev = self.lock.replace(other);
+        self.notify();
+        prev
+    }
+    fn notify(&self) {
+        self.generation.fetch_add(1, Ordering::Release);
+        // Taking the mutex orders the bump before waiters that checked the old generation go to sleep,
+        // so no wakeup is lost.
+        drop(self.waiters.lock().unwrap_or_else(PoisonError::into_inner));
+        self.changed.notify_all();
+    }
+}
+impl<const B: isize, T: Copy> WatchedSeqLockEx<B, T> {
+    #[inline]
+    pub fn load(&self) -> T {
+        self.lock.load()
+    }
+}
+impl<const B: isize, T: Default> Default for WatchedSeqLockEx<B, T> {
+    #[inline]
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+impl<const B: isize, T: core::fmt::Debug + Copy> core::fmt::Debug for WatchedSeqLockEx<B, T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("WatchedSeqLock")
+            .field("data", &self.load())
+            .field("generation", &self.generation())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn waiter_wakes_up_on_store() {
+        let lock = WatchedSeqLock::new(0u32);
+        let last_seen = lock.generation();
+        std::thread::scope(|s| {
+            let waiter = s.spawn(|| {
+                let generation = lock.wait_for_change(last_seen);
+                (generation, lock.load())
+            });
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            lock.store(7);
+            assert_eq!(waiter.join().unwrap(), (last_seen + 1, 7));
+        });
+    }
+
+    #[test]
+    fn returns_immediately_if_already_changed() {
+        let lock = WatchedSeqLock::new(0u32);
+        let last_seen = lock.generation();
+        lock.replace(1);
+        lock.access_mut(|v| *v += 1);
+        assert_eq!(lock.wait_for_change(last_seen), last_seen + 2);
+        assert_eq!(lock.load(), 2);
+    }
+}
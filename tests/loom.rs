@@ -0,0 +1,60 @@
+#![cfg(loom)]
+
+use atomics::{atomic_t::AtomicT32, spin_mutex::SpinMutex, spin_seqlock::SpinSeqLock};
+use loom::{
+    sync::{Arc, atomic::Ordering},
+    thread,
+};
+
+#[test]
+fn spin_mutex_increments() {
+    loom::model(|| {
+        let mutex = Arc::new(SpinMutex::new(0u32));
+        let handles: Vec<_> = (0..2)
+            .map(|_| {
+                let mutex = mutex.clone();
+                thread::spawn(move || *mutex.lock() += 1)
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(*mutex.lock(), 2);
+    });
+}
+
+#[test]
+fn spin_seqlock_reads_are_not_torn() {
+    loom::model(|| {
+        let lock = Arc::new(SpinSeqLock::new((0u32, 0u32)));
+        let writer = {
+            let lock = lock.clone();
+            thread::spawn(move || {
+                for i in 1..=2 {
+                    lock.store((i, i));
+                }
+            })
+        };
+        let (a, b) = lock.load();
+        assert_eq!(a, b);
+        writer.join().unwrap();
+        assert_eq!(lock.load(), (2, 2));
+    });
+}
+
+#[test]
+fn atomic_t_fetch_add() {
+    loom::model(|| {
+        let counter = Arc::new(AtomicT32::<u32>::new_safe(0));
+        let handles: Vec<_> = (0..2)
+            .map(|_| {
+                let counter = counter.clone();
+                thread::spawn(move || counter.fetch_add(1, Ordering::AcqRel))
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(counter.load(Ordering::Acquire), 2);
+    });
+}